@@ -1,45 +1,138 @@
 #![cfg(unix)]
 
-use crate::errors::AgentError;
-use crate::requests::{AccessTokenRequest, AccountsRequest, MyTokenRequest};
-use crate::responses::{AccessTokenResponse, MyTokenResponse};
-use crate::responses::{OIDCAgentResponse, Status};
+use crate::builder::AgentOptions;
+use crate::codec::{self, MessageDecoder, READ_CHUNK};
+#[cfg(feature = "async")]
+use crate::process::{AgentProcess, AgentStartup};
+use crate::generate::{AuthCodeFlow, Continuation, DeviceFlow, DevicePoll};
+use crate::requests::{
+    AccessTokenRequest, AccountInfoRequest, AccountsRequest, AddRequest, CheckRequest,
+    DeleteRequest, GenRequest, GenericRequest, IdTokenRequest, LockRequest, MyTokenRequest,
+    ReauthenticateRequest, RegisterRequest, RemoveAllRequest, RemoveRequest, ScopesRequest,
+    UnlockRequest,
+};
+use crate::responses::{
+    AccessTokenResponse, AccountInfo, IdTokenResponse, MyTokenResponse, RegisteredClient,
+    StatusResponse,
+};
+use crate::AccountConfig;
+#[cfg(feature = "async")]
+use crate::AgentBuilder;
 use crate::AgentResult;
+use crate::Error;
 use crate::Request;
+#[cfg(feature = "async")]
+use crate::socket::SocketAddress;
+use crate::transport::UnixTransport;
 use crate::Token;
-use std::env;
+use runtime::{with_timeout, AsyncStream};
+#[cfg(feature = "async")]
+use runtime::TokioIo;
+use std::collections::HashSet;
+#[cfg(feature = "async")]
+use std::ffi::OsStr;
 use std::fmt::Debug;
+#[cfg(feature = "async")]
 use std::path::Path;
-use std::path::PathBuf;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::UnixStream;
+
+/// Access token cache
+pub mod cache;
+/// Async runtime backends
+pub mod runtime;
+
+pub use cache::CachedAgent;
 
 #[derive(Debug, Clone)]
-pub struct Agent {
-    socket_path: PathBuf,
+pub struct Agent<T = UnixTransport> {
+    transport: T,
+    options: AgentOptions,
 }
 
+#[cfg(feature = "async")]
 impl Agent {
     /// Asynchronous version of the [`crate::Agent::new()`].
     pub async fn new() -> AgentResult<Self> {
-        let socket_var = env::var("OIDC_SOCK")?;
-        let socket_path = Path::new(&socket_var);
+        AgentBuilder::new().build_async().await
+    }
 
-        //Check the connection
-        UnixStream::connect(socket_path).await?;
-        Ok(Self {
-            socket_path: socket_path.into(),
-        })
+    /// Creates a new [`AgentBuilder`] to build an agent. Use the
+    /// [`AgentBuilder::build_async`] to build the asynchronous agent.
+    pub fn builder() -> AgentBuilder {
+        AgentBuilder::new()
+    }
+
+    /// Asynchronous version of the [`crate::Agent::from_startup_output()`].
+    pub async fn from_startup_output(output: &str) -> AgentResult<(Self, u32)> {
+        let startup = AgentStartup::parse(output)?;
+        let agent = startup.builder().build_async().await?;
+        Ok((agent, startup.pid()))
+    }
+
+    /// Asynchronous version of the [`crate::Agent::spawn()`].
+    ///
+    /// Starting the agent process blocks the current thread until the agent daemonizes.
+    pub async fn spawn() -> AgentResult<(Self, AgentProcess)> {
+        Self::spawn_with_args(std::iter::empty::<&OsStr>()).await
+    }
+
+    /// Asynchronous version of the [`crate::Agent::spawn_with_args()`].
+    pub async fn spawn_with_args<I, S>(args: I) -> AgentResult<(Self, AgentProcess)>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let process = AgentProcess::spawn(args)?;
+        let agent = process.startup().builder().build_async().await?;
+        Ok((agent, process))
+    }
+
+    pub(crate) fn from_parts(socket: SocketAddress, options: AgentOptions) -> Self {
+        Self {
+            transport: UnixTransport::with_options(socket, options.clone()),
+            options,
+        }
     }
 
     /// Retrives the agent socket path.
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let agent = Agent::new()?;
     /// assert_eq!(agent.get_socket_path(), Some("/tmp/oidc-agent-service-1000/oidc-agent.sock"))
     /// ```
     pub fn get_socket_path(&self) -> Option<&str> {
-        self.socket_path.to_str()
+        self.transport.socket().as_path().and_then(Path::to_str)
+    }
+
+    /// Retrives the agent socket address.
+    pub fn get_socket_address(&self) -> &SocketAddress {
+        self.transport.socket()
+    }
+}
+
+impl<T: AsyncTransport> Agent<T> {
+    /// Asynchronous version of the [`crate::Agent::with_transport()`].
+    pub fn with_transport(transport: T) -> Self {
+        Self::from_transport(transport, AgentOptions::default())
+    }
+
+    pub(crate) fn from_transport(transport: T, options: AgentOptions) -> Self {
+        Self { transport, options }
+    }
+
+    /// Returns the transport of the agent.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub(crate) async fn connect(&self) -> AgentResult<T::Stream> {
+        self.transport.connect().await
+    }
+
+    /// Asynchronous version of [`crate::Agent::status()`].
+    pub async fn status(&self) -> AgentResult<StatusResponse> {
+        let request = CheckRequest::new();
+        let response = self.send_request(request).await?;
+        Ok(response)
     }
 
     /// Asynchronous version of [`crate::Agent::get_access_token()`].
@@ -59,6 +152,20 @@ impl Agent {
         Ok(response)
     }
 
+    /// Asynchronous version of [`crate::Agent::get_id_token()`].
+    pub async fn get_id_token(&self, account_shortname: &str) -> AgentResult<Token> {
+        let request = IdTokenRequest::basic(account_shortname);
+        let response = self.send_request(request).await?;
+        Ok(response.id_token().clone())
+    }
+
+    /// Asynchronous version of [`crate::Agent::get_id_token_full()`].
+    pub async fn get_id_token_full(&self, account_shortname: &str) -> AgentResult<IdTokenResponse> {
+        let request = IdTokenRequest::basic(account_shortname);
+        let response = self.send_request(request).await?;
+        Ok(response)
+    }
+
     /// Asynchronous version of [`crate::Agent::get_mytoken()`].
     pub async fn get_mytoken(&self, account_shortname: &str) -> AgentResult<Token> {
         let request = MyTokenRequest::basic(account_shortname);
@@ -80,27 +187,255 @@ impl Agent {
         Ok(response.info().clone())
     }
 
+    /// Asynchronous version of [`crate::Agent::get_accounts_info()`].
+    pub async fn get_accounts_info(&self) -> AgentResult<Vec<AccountInfo>> {
+        let request = AccountInfoRequest::new();
+        let response = self.send_request(request).await?;
+        Ok(response.accounts())
+    }
+
+    /// Asynchronous version of [`crate::Agent::lock()`].
+    pub async fn lock(&self, password: &str) -> AgentResult<()> {
+        let request = LockRequest::new(password);
+        self.send_request(request).await?;
+        Ok(())
+    }
+
+    /// Asynchronous version of [`crate::Agent::unlock()`].
+    pub async fn unlock(&self, password: &str) -> AgentResult<()> {
+        let request = UnlockRequest::new(password);
+        self.send_request(request).await?;
+        Ok(())
+    }
+
+    /// Asynchronous version of [`crate::Agent::add_account()`].
+    pub async fn add_account(&self, config: &AccountConfig) -> AgentResult<()> {
+        let request = AddRequest::builder(config).build()?;
+        self.send_request(request).await?;
+        Ok(())
+    }
+
+    /// Asynchronous version of [`crate::Agent::remove_account()`].
+    pub async fn remove_account(&self, account_shortname: &str) -> AgentResult<()> {
+        let request = RemoveRequest::new(account_shortname);
+        self.send_request(request).await?;
+        Ok(())
+    }
+
+    /// Asynchronous version of [`crate::Agent::remove_all_accounts()`].
+    pub async fn remove_all_accounts(&self) -> AgentResult<()> {
+        let request = RemoveAllRequest::new();
+        self.send_request(request).await?;
+        Ok(())
+    }
+
+    /// Asynchronous version of [`crate::Agent::revoke_account_tokens()`].
+    pub async fn revoke_account_tokens(&self, config: &AccountConfig) -> AgentResult<()> {
+        let request = DeleteRequest::new(config);
+        self.send_request(request)
+            .await
+            .map_err(Error::into_revocation_error)?;
+        Ok(())
+    }
+
+    /// Asynchronous version of [`crate::Agent::start_device_flow()`].
+    pub async fn start_device_flow(&self, request: GenRequest) -> AgentResult<DeviceFlow> {
+        let response = self.send_request(request).await?;
+        DeviceFlow::from_response(response)
+    }
+
+    /// Asynchronous version of [`crate::Agent::device_lookup()`].
+    pub async fn device_lookup(&self, flow: &mut DeviceFlow) -> AgentResult<DevicePoll> {
+        let result = self.send_request(flow.lookup_request()).await;
+        flow.handle_lookup(result)
+    }
+
+    /// Asynchronous version of [`crate::Agent::poll_device_flow()`].
+    pub async fn poll_device_flow(&self, flow: &mut DeviceFlow) -> AgentResult<AccountConfig> {
+        loop {
+            match self.device_lookup(flow).await? {
+                DevicePoll::Pending(interval) => T::Stream::sleep(interval).await,
+                DevicePoll::Done(config) => return Ok(config),
+            }
+        }
+    }
+
+    /// Asynchronous version of [`crate::Agent::start_auth_code_flow()`].
+    pub async fn start_auth_code_flow(&self, request: GenRequest) -> AgentResult<AuthCodeFlow> {
+        let response = self.send_request(request).await?;
+        AuthCodeFlow::from_response(response)
+    }
+
+    /// Asynchronous version of [`crate::Agent::finish_auth_code_flow()`].
+    pub async fn finish_auth_code_flow(
+        &self,
+        flow: &AuthCodeFlow,
+        redirected_uri: &str,
+    ) -> AgentResult<AccountConfig> {
+        let request = flow.code_exchange_request(redirected_uri)?;
+        let response = self.send_request(request).await?;
+        AuthCodeFlow::handle_result(response)
+    }
+
+    /// Asynchronous version of [`crate::Agent::state_lookup()`].
+    pub async fn state_lookup(&self, flow: &AuthCodeFlow) -> AgentResult<Option<AccountConfig>> {
+        match self.send_request(flow.state_lookup_request()).await {
+            Ok(response) => AuthCodeFlow::handle_result(response).map(Some),
+            Err(Error::NotFoundError(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Asynchronous version of [`crate::Agent::register_client()`].
+    pub async fn register_client(&self, request: RegisterRequest) -> AgentResult<RegisteredClient> {
+        let response = self.send_request(request).await?;
+        Ok(response.client().clone())
+    }
+
+    /// Asynchronous version of [`crate::Agent::reauthenticate()`].
+    pub async fn reauthenticate(&self, account_shortname: &str) -> AgentResult<Continuation> {
+        let request = ReauthenticateRequest::new(account_shortname);
+        let response = self.send_request(request).await?;
+        Continuation::from_response(response)
+    }
+
+    /// Asynchronous version of [`crate::Agent::get_supported_scopes()`].
+    pub async fn get_supported_scopes(&self, issuer: &str) -> AgentResult<HashSet<String>> {
+        let request = ScopesRequest::builder().issuer(issuer)?.build()?;
+        let response = self.send_request(request).await?;
+        Ok(response.scopes())
+    }
+
+    /// Asynchronous version of [`crate::Agent::send_raw()`].
+    pub async fn send_raw(&self, request: serde_json::Value) -> AgentResult<serde_json::Value> {
+        let request = GenericRequest::<serde_json::Value>::new(request)?;
+        self.send_request(request).await
+    }
+
     /// Asynchronous version of [`crate::Agent::send_request()`].
-    pub async fn send_request<T>(&self, request: T) -> AgentResult<T::SuccessResponse>
+    pub async fn send_request<R>(&self, request: R) -> AgentResult<R::SuccessResponse>
+    where
+        R: Request,
+    {
+        self.connection().await?.send_request(request).await
+    }
+
+    /// Asynchronous version of [`crate::Agent::connection()`].
+    pub async fn connection(&self) -> AgentResult<Connection<T::Stream>> {
+        let stream = self.connect().await?;
+        Ok(Connection {
+            stream,
+            decoder: MessageDecoder::default(),
+            options: self.options.clone(),
+        })
+    }
+}
+
+/// Asynchronous version of the [`crate::Transport`]. The [`AsyncStream`] determines the async
+/// runtime the agent runs on.
+pub trait AsyncTransport {
+    type Stream: AsyncStream;
+
+    /// Attempts to open a new stream to the agent.
+    fn connect(&self) -> impl Future<Output = AgentResult<Self::Stream>> + Send;
+}
+
+/// Asynchronous version of the [`crate::provider::TokenProvider`].
+pub trait AsyncTokenProvider: Sync {
+    /// Asynchronous version of [`crate::provider::TokenProvider::fetch_access_token()`].
+    fn fetch_access_token(
+        &self,
+        account_shortname: &str,
+    ) -> impl Future<Output = AgentResult<Token>> + Send {
+        async move {
+            let request = AccessTokenRequest::basic(account_shortname);
+            let response = self.fetch_access_token_response(request).await?;
+            Ok(response.access_token().clone())
+        }
+    }
+
+    /// Asynchronous version of [`crate::provider::TokenProvider::fetch_access_token_response()`].
+    fn fetch_access_token_response(
+        &self,
+        request: AccessTokenRequest,
+    ) -> impl Future<Output = AgentResult<AccessTokenResponse>> + Send;
+
+    /// Asynchronous version of [`crate::provider::TokenProvider::fetch_mytoken()`].
+    fn fetch_mytoken(
+        &self,
+        account_shortname: &str,
+    ) -> impl Future<Output = AgentResult<Token>> + Send;
+}
+
+impl<T: AsyncTransport + Sync> AsyncTokenProvider for Agent<T> {
+    async fn fetch_access_token_response(
+        &self,
+        request: AccessTokenRequest,
+    ) -> AgentResult<AccessTokenResponse> {
+        self.send_request(request).await
+    }
+
+    async fn fetch_mytoken(&self, account_shortname: &str) -> AgentResult<Token> {
+        self.get_mytoken(account_shortname).await
+    }
+}
+
+/// Connects to the agent socket with tokio. See the [`runtime::SmolTransport`] for the `smol`
+/// runtime.
+#[cfg(feature = "async")]
+impl AsyncTransport for UnixTransport {
+    type Stream = TokioIo<tokio::net::UnixStream>;
+
+    async fn connect(&self) -> AgentResult<Self::Stream> {
+        let connect = self.socket().connect_async();
+        let stream =
+            with_timeout::<Self::Stream, _, _>(self.options.connect_timeout, connect).await?;
+        self.verify(&stream)?;
+        Ok(TokioIo(stream))
+    }
+}
+
+/// Asynchronous version of the [`crate::Connection`].
+#[derive(Debug)]
+pub struct Connection<S> {
+    stream: S,
+    decoder: MessageDecoder,
+    options: AgentOptions,
+}
+
+impl<S: AsyncStream> Connection<S> {
+    /// Asynchronous version of [`crate::Connection::send_request()`].
+    pub async fn send_request<T>(&mut self, mut request: T) -> AgentResult<T::SuccessResponse>
     where
         T: Request,
     {
-        let mut socket = UnixStream::connect(Path::new(&self.socket_path)).await?;
-        let req = serde_json::to_vec(&request)?;
-        socket.write_all(&req).await?;
-
-        let mut buffer = Vec::new();
-        socket.read_to_end(&mut buffer).await?;
-        let resp: OIDCAgentResponse = serde_json::from_slice(&buffer)?;
-        match resp.status() {
-            Status::SUCCESS => {
-                let r: T::SuccessResponse = serde_json::from_slice(&buffer)?;
-                Ok(r)
+        self.options.apply(&mut request);
+        let req = codec::encode_request(&request)?;
+        with_timeout::<S, _, _>(self.options.write_timeout, self.stream.write_all(&req)).await?;
+        let message = self.read_message().await?;
+        codec::decode_response(&message)
+    }
+
+    /// Asynchronous version of [`crate::Connection::send_raw()`].
+    pub async fn send_raw(&mut self, request: serde_json::Value) -> AgentResult<serde_json::Value> {
+        let request = GenericRequest::<serde_json::Value>::new(request)?;
+        self.send_request(request).await
+    }
+
+    /// Reads exactly one complete message from the stream.
+    async fn read_message(&mut self) -> AgentResult<Vec<u8>> {
+        let mut chunk = [0; READ_CHUNK];
+        loop {
+            if let Some(message) = self.decoder.next_message()? {
+                return Ok(message);
             }
-            Status::FAILURE => {
-                let r: AgentError = serde_json::from_slice(&buffer)?;
-                Err(r.into())
+            let read =
+                with_timeout::<S, _, _>(self.options.read_timeout, self.stream.read(&mut chunk))
+                    .await?;
+            if read == 0 {
+                return Err(self.decoder.closed_error().into());
             }
+            self.decoder.feed(&chunk[..read]);
         }
     }
 }
@@ -0,0 +1,311 @@
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+use crate::async_impl::{Agent, AsyncTokenProvider};
+use crate::cache::{is_fresh, CacheKey, TokenCache};
+use crate::requests::AccessTokenRequest;
+use crate::responses::AccessTokenResponse;
+use crate::{AgentResult, Token};
+
+#[derive(Debug)]
+enum Slot {
+    Ready(AccessTokenResponse),
+    /// The token valid for at least the `validity` is being requested, the wakers belong to the
+    /// callers waiting for it.
+    Pending {
+        validity: Duration,
+        wakers: Vec<Waker>,
+    },
+}
+
+/// Asynchronous version of the [`crate::CachedAgent`].
+///
+/// Concurrent callers asking for the same token share one request to the provider: the first one
+/// sends it, the others wait for its response and take it even if the token expires within their
+/// minimum validity. Only the callers requiring a longer validity than the request in flight send
+/// their own request afterwards. If the request fails or the first caller is cancelled, the next
+/// waiting caller sends the request again.
+///
+/// # Examples
+/// ```ignore
+/// let agent = Arc::new(CachedAgent::new(Agent::new().await?));
+/// let tasks: Vec<_> = (0..10)
+///     .map(|_| {
+///         let agent = agent.clone();
+///         tokio::spawn(async move { agent.get_access_token("profile_shortname").await })
+///     })
+///     .collect();
+/// // the agent receives one request only
+/// ```
+#[derive(Debug)]
+pub struct CachedAgent<P = Agent> {
+    provider: P,
+    cache: TokenCache<Slot>,
+}
+
+impl<P: AsyncTokenProvider> CachedAgent<P> {
+    /// Creates a new `CachedAgent` with an empty cache and the
+    /// [`crate::cache::DEFAULT_MIN_VALIDITY`].
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider,
+            cache: TokenCache::new(),
+        }
+    }
+    /// Sets the minimum time the cached token must stay valid to be served.
+    pub fn min_validity(mut self, min_validity: Duration) -> Self {
+        self.cache.set_min_validity(min_validity);
+        self
+    }
+
+    /// Returns the wrapped provider.
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// Asynchronous version of [`crate::CachedAgent::get_access_token()`].
+    pub async fn get_access_token(&self, account_shortname: &str) -> AgentResult<Token> {
+        self.fetch_access_token(account_shortname).await
+    }
+
+    /// Asynchronous version of [`crate::CachedAgent::get_access_token_full()`].
+    pub async fn get_access_token_full(
+        &self,
+        account_shortname: &str,
+    ) -> AgentResult<AccessTokenResponse> {
+        self.fetch_access_token_response(AccessTokenRequest::basic(account_shortname))
+            .await
+    }
+
+    /// Removes the token requested by the `request` from the cache. A request in flight is not
+    /// affected.
+    pub fn invalidate(&self, request: &AccessTokenRequest) {
+        let key = CacheKey::from_request(request);
+        let mut entries = self.cache.entries();
+        if let Some(Slot::Ready(_)) = entries.get(&key) {
+            entries.remove(&key);
+        }
+    }
+
+    /// Removes every token from the cache. The requests in flight are not affected.
+    pub fn clear(&self) {
+        self.cache
+            .entries()
+            .retain(|_, slot| matches!(slot, Slot::Pending { .. }));
+    }
+
+    /// Returns the fresh cached token or the response of the joined request in flight, or `None`
+    /// once the caller is the one to request it.
+    async fn cached_or_claim(
+        &self,
+        key: &CacheKey,
+        validity: Duration,
+    ) -> Option<AccessTokenResponse> {
+        let mut joined = false;
+        loop {
+            {
+                let mut entries = self.cache.entries();
+                match entries.get(key) {
+                    Some(Slot::Ready(response)) if joined || is_fresh(response, validity) => {
+                        return Some(response.clone());
+                    }
+                    Some(Slot::Pending {
+                        validity: pending, ..
+                    }) => joined = *pending >= validity,
+                    _ => {
+                        let pending = Slot::Pending {
+                            validity,
+                            wakers: Vec::new(),
+                        };
+                        entries.insert(key.clone(), pending);
+                        return None;
+                    }
+                }
+            }
+            self.in_flight(key).await;
+        }
+    }
+
+    /// Waits until the request in flight for the `key` finishes.
+    async fn in_flight(&self, key: &CacheKey) {
+        std::future::poll_fn(|cx| match self.cache.entries().get_mut(key) {
+            Some(Slot::Pending { wakers, .. }) => {
+                if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            _ => Poll::Ready(()),
+        })
+        .await
+    }
+}
+
+impl<P: AsyncTokenProvider> AsyncTokenProvider for CachedAgent<P> {
+    async fn fetch_access_token_response(
+        &self,
+        mut request: AccessTokenRequest,
+    ) -> AgentResult<AccessTokenResponse> {
+        let key = CacheKey::from_request(&request);
+        let validity = self.cache.required_validity(&mut request);
+        if let Some(response) = self.cached_or_claim(&key, validity).await {
+            return Ok(response);
+        }
+        let _claim = Claim {
+            cache: &self.cache,
+            key: &key,
+        };
+        let response = self.provider.fetch_access_token_response(request).await?;
+        let previous = self
+            .cache
+            .entries()
+            .insert(key.clone(), Slot::Ready(response.clone()));
+        if let Some(Slot::Pending { wakers, .. }) = previous {
+            wakers.into_iter().for_each(Waker::wake);
+        }
+        Ok(response)
+    }
+
+    async fn fetch_mytoken(&self, account_shortname: &str) -> AgentResult<Token> {
+        self.provider.fetch_mytoken(account_shortname).await
+    }
+}
+
+/// Releases the pending slot when the request failed or the caller was cancelled, so one of the
+/// waiting callers requests the token instead.
+struct Claim<'a> {
+    cache: &'a TokenCache<Slot>,
+    key: &'a CacheKey,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        let mut entries = self.cache.entries();
+        if let Some(Slot::Pending { .. }) = entries.get(self.key)
+            && let Some(Slot::Pending { wakers, .. }) = entries.remove(self.key)
+        {
+            drop(entries);
+            wakers.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use url::Url;
+
+    /// Provider answering after a delay with tokens numbered by the call.
+    struct SlowProvider {
+        calls: AtomicUsize,
+        failures: usize,
+        lifetime: chrono::Duration,
+    }
+
+    impl SlowProvider {
+        fn new(lifetime: chrono::Duration) -> Self {
+            Self {
+                calls: AtomicUsize::new(0),
+                failures: 0,
+                lifetime,
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl AsyncTokenProvider for SlowProvider {
+        async fn fetch_access_token_response(
+            &self,
+            _request: AccessTokenRequest,
+        ) -> AgentResult<AccessTokenResponse> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            if call < self.failures {
+                return Err("The provider failed!".into());
+            }
+            Ok(AccessTokenResponse::new(
+                Token(format!("token-{}", call)),
+                Url::parse("https://issuer.example").unwrap(),
+                Utc::now() + self.lifetime,
+            ))
+        }
+
+        async fn fetch_mytoken(&self, _account_shortname: &str) -> AgentResult<Token> {
+            Err("The provider has no mytoken!".into())
+        }
+    }
+
+    async fn fetch_concurrently(
+        agent: &Arc<CachedAgent<SlowProvider>>,
+        callers: usize,
+    ) -> Vec<AgentResult<String>> {
+        let tasks: Vec<_> = (0..callers)
+            .map(|_| {
+                let agent = agent.clone();
+                tokio::spawn(async move {
+                    let token = agent.get_access_token("profile").await?;
+                    Ok(token.secret().to_string())
+                })
+            })
+            .collect();
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn shares_request_in_flight() {
+        let agent = Arc::new(CachedAgent::new(SlowProvider::new(
+            chrono::Duration::hours(1),
+        )));
+        for result in fetch_concurrently(&agent, 10).await {
+            assert_eq!(result.unwrap(), "token-0");
+        }
+        assert_eq!(agent.provider().calls(), 1);
+
+        assert_eq!(
+            agent.get_access_token("profile").await.unwrap().secret(),
+            "token-0"
+        );
+        assert_eq!(agent.provider().calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn shares_short_lived_token_in_flight() {
+        let agent = Arc::new(CachedAgent::new(SlowProvider::new(
+            chrono::Duration::seconds(30),
+        )));
+        for result in fetch_concurrently(&agent, 10).await {
+            assert_eq!(result.unwrap(), "token-0");
+        }
+        assert_eq!(agent.provider().calls(), 1);
+
+        // Not fresh for the later callers anymore.
+        assert_eq!(
+            agent.get_access_token("profile").await.unwrap().secret(),
+            "token-1"
+        );
+        assert_eq!(agent.provider().calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_after_failed_request() {
+        let mut provider = SlowProvider::new(chrono::Duration::hours(1));
+        provider.failures = 1;
+        let agent = Arc::new(CachedAgent::new(provider));
+        let results = fetch_concurrently(&agent, 3).await;
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+        for result in results.into_iter().filter_map(Result::ok) {
+            assert_eq!(result, "token-1");
+        }
+        assert_eq!(agent.provider().calls(), 2);
+    }
+}
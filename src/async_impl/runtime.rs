@@ -0,0 +1,143 @@
+use std::io;
+use std::pin::pin;
+use std::task::Poll;
+use std::time::Duration;
+
+#[cfg(feature = "smol")]
+use crate::async_impl::AsyncTransport;
+#[cfg(feature = "smol")]
+use crate::socket::SocketAddress;
+#[cfg(feature = "smol")]
+use crate::transport::UnixTransport;
+#[cfg(feature = "smol")]
+use crate::AgentResult;
+#[cfg(feature = "smol")]
+use std::os::unix::net::UnixStream;
+
+/// Stream to the agent driven by an async runtime.
+///
+/// The stream ties the [`crate::async_impl::Agent`] to the runtime: the IO and the timers used for
+/// the timeouts and the polling come from it. Wrap the streams of the supported runtimes in the
+/// [`TokioIo`] (the `async` feature) or the [`FuturesIo`] (the `smol` feature), or implement the
+/// trait for other runtimes.
+pub trait AsyncStream: Send {
+    /// Reads some bytes into the `buf`, returns `0` when the agent closed the stream.
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>> + Send;
+
+    /// Writes the whole `data` to the stream.
+    fn write_all(&mut self, data: &[u8]) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Waits for the `duration` with the timer of the runtime.
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send;
+}
+
+/// Stream of the tokio runtime, e.g. the [`tokio::net::UnixStream`].
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct TokioIo<S>(pub S);
+
+#[cfg(feature = "async")]
+impl<S> AsyncStream for TokioIo<S>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        tokio::io::AsyncReadExt::read(&mut self.0, buf).await
+    }
+
+    async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        tokio::io::AsyncWriteExt::write_all(&mut self.0, data).await
+    }
+
+    async fn sleep(duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+/// Stream implementing the `futures-io` traits, driven by the `async-io` reactor used by `smol`
+/// and `async-std`.
+#[cfg(feature = "smol")]
+#[derive(Debug)]
+pub struct FuturesIo<S>(pub S);
+
+#[cfg(feature = "smol")]
+impl<S> AsyncStream for FuturesIo<S>
+where
+    S: futures_lite::AsyncRead + futures_lite::AsyncWrite + Unpin + Send,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        futures_lite::AsyncReadExt::read(&mut self.0, buf).await
+    }
+
+    async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        futures_lite::AsyncWriteExt::write_all(&mut self.0, data).await
+    }
+
+    async fn sleep(duration: Duration) {
+        async_io::Timer::after(duration).await;
+    }
+}
+
+/// Transport connecting to the agent Unix socket with the `async-io` reactor, for `smol` and
+/// `async-std` applications. Build the agent with the [`crate::AgentBuilder::build_smol`].
+#[cfg(feature = "smol")]
+#[derive(Debug, Clone)]
+pub struct SmolTransport(UnixTransport);
+
+#[cfg(feature = "smol")]
+impl SmolTransport {
+    /// Returns the agent socket address.
+    pub fn socket(&self) -> &SocketAddress {
+        self.0.socket()
+    }
+}
+
+#[cfg(feature = "smol")]
+impl From<UnixTransport> for SmolTransport {
+    fn from(transport: UnixTransport) -> Self {
+        Self(transport)
+    }
+}
+
+#[cfg(feature = "smol")]
+impl AsyncTransport for SmolTransport {
+    type Stream = FuturesIo<async_io::Async<UnixStream>>;
+
+    async fn connect(&self) -> AgentResult<Self::Stream> {
+        let connect = async {
+            match self.0.socket() {
+                SocketAddress::Path(path) => async_io::Async::<UnixStream>::connect(path).await,
+                // async-io connects only to paths, connecting to a local socket does not block.
+                abstract_socket => async_io::Async::new(abstract_socket.connect(None)?),
+            }
+        };
+        let stream =
+            with_timeout::<Self::Stream, _, _>(self.0.options.connect_timeout, connect).await?;
+        self.0.verify(&stream)?;
+        Ok(FuturesIo(stream))
+    }
+}
+
+/// Awaits the IO `future`, failing with [`io::ErrorKind::TimedOut`] after the `timeout` measured
+/// by the timer of the `S` runtime.
+pub(crate) async fn with_timeout<S, F, T>(timeout: Option<Duration>, future: F) -> io::Result<T>
+where
+    S: AsyncStream,
+    F: Future<Output = io::Result<T>>,
+{
+    let Some(timeout) = timeout else {
+        return future.await;
+    };
+    let mut future = pin!(future);
+    let mut sleep = pin!(S::sleep(timeout));
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(result) = future.as_mut().poll(cx) {
+            return Poll::Ready(result);
+        }
+        match sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::ErrorKind::TimedOut.into())),
+            Poll::Pending => Poll::Pending,
+        }
+    })
+    .await
+}
@@ -0,0 +1,256 @@
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::discovery::{self, DiscoveryReport};
+use crate::socket::SocketAddress;
+use crate::transport::Transport;
+#[cfg(any(feature = "async", feature = "smol"))]
+use crate::async_impl::AsyncTransport;
+use crate::{Agent, AgentResult};
+
+/// Default name of the environment variable holding the agent socket path.
+pub const DEFAULT_SOCKET_ENV_VAR: &str = "OIDC_SOCK";
+
+/// Options applied to every connection made by the agent.
+#[derive(Debug, Clone)]
+pub(crate) struct AgentOptions {
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) read_timeout: Option<Duration>,
+    pub(crate) write_timeout: Option<Duration>,
+    pub(crate) application_hint: Option<String>,
+    pub(crate) verify_peer: bool,
+}
+
+impl Default for AgentOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            application_hint: None,
+            verify_peer: true,
+        }
+    }
+}
+
+impl AgentOptions {
+    /// Sets the default `application_hint` for the request, unless the request already has one.
+    pub(crate) fn apply<T: crate::Request>(&self, request: &mut T) {
+        if let (Some(hint), Some(request_hint @ None)) =
+            (&self.application_hint, request.application_hint_mut())
+        {
+            *request_hint = Some(hint.clone());
+        }
+    }
+}
+
+/// Builder of the [`Agent`] and the [`crate::async_impl::Agent`].
+///
+/// By default, the socket path is discovered with [`discovery::discover`], no timeouts are set
+/// and the connection is checked while building, just like [`Agent::new`] does.
+///
+/// # Examples
+/// ```ignore
+/// let agent = Agent::builder()
+///     .socket_path("/run/user/1000/oidc-agent/oidc-agent.sock")
+///     .timeout(Duration::from_secs(5))
+///     .application_hint("my-service")
+///     .lazy()
+///     .build()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct AgentBuilder {
+    socket_path: Option<SocketAddress>,
+    env_var: String,
+    discovery: bool,
+    lazy: bool,
+    options: AgentOptions,
+}
+
+impl Default for AgentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AgentBuilder {
+    /// Creates a new builder with the default settings.
+    pub fn new() -> Self {
+        Self {
+            socket_path: None,
+            env_var: DEFAULT_SOCKET_ENV_VAR.to_string(),
+            discovery: true,
+            lazy: false,
+            options: AgentOptions::default(),
+        }
+    }
+    /// Sets the agent socket path explicitly. The environment variable is not read then.
+    ///
+    /// On Linux, a path starting with `@`, e.g. `@oidc-agent`, sets a socket in the abstract
+    /// namespace, see [`SocketAddress`].
+    pub fn socket_path<P: AsRef<Path>>(mut self, socket_path: P) -> Self {
+        self.socket_path = Some(SocketAddress::from(socket_path.as_ref()));
+        self
+    }
+    /// Sets the agent socket address explicitly. The environment variable is not read then.
+    pub fn socket_address(mut self, socket_address: SocketAddress) -> Self {
+        self.socket_path = Some(socket_address);
+        self
+    }
+    /// Sets the name of the environment variable holding the socket path. Defaults to
+    /// `OIDC_SOCK`.
+    pub fn env_var<T: ToString>(mut self, env_var: T) -> Self {
+        self.env_var = env_var.to_string();
+        self
+    }
+    /// Disables the fallback to the default `oidc-agent-service` socket locations. Only the
+    /// environment variable is read then.
+    pub fn without_discovery(mut self) -> Self {
+        self.discovery = false;
+        self
+    }
+    /// Disables the verification of the socket owner, its permissions and the uid of the agent
+    /// process, see [`crate::peer`]. The verification is enabled by default.
+    pub fn without_peer_verification(mut self) -> Self {
+        self.options.verify_peer = false;
+        self
+    }
+    /// Sets the maximum time of connecting to the agent socket.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.options.connect_timeout = Some(timeout);
+        self
+    }
+    /// Sets the maximum time of waiting for the agent response.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.options.read_timeout = Some(timeout);
+        self
+    }
+    /// Sets the maximum time of sending the request to the agent.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.options.write_timeout = Some(timeout);
+        self
+    }
+    /// Sets the connect, read and write timeouts at once.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.connect_timeout(timeout)
+            .read_timeout(timeout)
+            .write_timeout(timeout)
+    }
+    /// Skips the connection check while building. The first connection is made by the first
+    /// request then.
+    pub fn lazy(mut self) -> Self {
+        self.lazy = true;
+        self
+    }
+    /// Checks the connection while building. This is the default.
+    pub fn eager(mut self) -> Self {
+        self.lazy = false;
+        self
+    }
+    /// Sets the `application_hint` added to every request supporting it, unless the request
+    /// already has its own.
+    pub fn application_hint<T: ToString>(mut self, application_hint: T) -> Self {
+        self.options.application_hint = Some(application_hint.to_string());
+        self
+    }
+
+    /// Runs the socket discovery with the builder settings, without building the agent. Unless
+    /// the builder is lazy, the candidates are connected to with the connect timeout and the peer
+    /// verification settings.
+    pub fn discover_socket(&self) -> DiscoveryReport {
+        discovery::discover_with(&self.env_var, (!self.lazy).then_some(&self.options))
+    }
+
+    /// Returns the socket address and whether the connection was already checked and verified.
+    fn resolve_socket_path(&self) -> AgentResult<(SocketAddress, bool)> {
+        if let Some(socket_path) = &self.socket_path {
+            return Ok((socket_path.clone(), false));
+        }
+        if !self.discovery {
+            return Ok((
+                SocketAddress::from(env::var(&self.env_var)?.as_str()),
+                false,
+            ));
+        }
+        let report = self.discover_socket();
+        match report.selected() {
+            Some(socket) => Ok((socket.clone(), true)),
+            None => Err(report.into_error()),
+        }
+    }
+
+    /// Attempts to build the [`Agent`].
+    /// # Errors
+    /// The method returns an coresponding [`crate::Error`] if:
+    /// - no socket path was set and the discovery found no usable socket, if the environment
+    ///   variable is set, its rejection is returned, e.g. the [`crate::Error::PeerVerificationError`],
+    /// - no socket path was set, the discovery is disabled and the environment variable is not set
+    ///   or cannot be retrieved,
+    /// - the connection is checked and connecting to the socket is not possible.
+    pub fn build(self) -> AgentResult<Agent> {
+        let (socket_path, checked) = self.resolve_socket_path()?;
+        let agent = Agent::from_parts(socket_path, self.options);
+        if !self.lazy && !checked {
+            agent.connect()?;
+        }
+        Ok(agent)
+    }
+
+    /// Attempts to build the [`Agent`] sending the requests over the custom `transport`. Only the
+    /// `application_hint` and the connection check settings are used, the `transport` is
+    /// responsible for the rest.
+    /// # Errors
+    /// The method returns an coresponding [`crate::Error`] if:
+    /// - the connection is checked and the `transport` fails to connect.
+    pub fn build_with_transport<T: Transport>(self, transport: T) -> AgentResult<Agent<T>> {
+        let agent = Agent::from_transport(transport, self.options);
+        if !self.lazy {
+            agent.connect()?;
+        }
+        Ok(agent)
+    }
+
+    /// Asynchronous version of the [`AgentBuilder::build`] building the
+    /// [`crate::async_impl::Agent`].
+    #[cfg(feature = "async")]
+    pub async fn build_async(self) -> AgentResult<crate::async_impl::Agent> {
+        let (socket_path, checked) = self.resolve_socket_path()?;
+        let agent = crate::async_impl::Agent::from_parts(socket_path, self.options);
+        if !self.lazy && !checked {
+            agent.connect().await?;
+        }
+        Ok(agent)
+    }
+
+    /// Asynchronous version of the [`AgentBuilder::build`] building the
+    /// [`crate::async_impl::Agent`] running on `smol` or `async-std`, see the
+    /// [`crate::async_impl::runtime::SmolTransport`].
+    #[cfg(feature = "smol")]
+    pub async fn build_smol(
+        self,
+    ) -> AgentResult<crate::async_impl::Agent<crate::async_impl::runtime::SmolTransport>> {
+        let (socket_path, checked) = self.resolve_socket_path()?;
+        let transport =
+            crate::transport::UnixTransport::with_options(socket_path, self.options.clone());
+        let agent = crate::async_impl::Agent::from_transport(transport.into(), self.options);
+        if !self.lazy && !checked {
+            agent.connect().await?;
+        }
+        Ok(agent)
+    }
+
+    /// Asynchronous version of the [`AgentBuilder::build_with_transport`] building the
+    /// [`crate::async_impl::Agent`]. The read and write timeouts are applied as well.
+    #[cfg(any(feature = "async", feature = "smol"))]
+    pub async fn build_async_with_transport<T: AsyncTransport>(
+        self,
+        transport: T,
+    ) -> AgentResult<crate::async_impl::Agent<T>> {
+        let agent = crate::async_impl::Agent::from_transport(transport, self.options);
+        if !self.lazy {
+            agent.connect().await?;
+        }
+        Ok(agent)
+    }
+}
@@ -0,0 +1,287 @@
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use url::Url;
+
+use crate::provider::TokenProvider;
+use crate::requests::AccessTokenRequest;
+use crate::responses::AccessTokenResponse;
+use crate::{Agent, AgentResult, Token};
+
+/// Default minimum validity of the cached access tokens.
+pub const DEFAULT_MIN_VALIDITY: Duration = Duration::from_secs(60);
+
+/// Identity of the cached access token: the account or the issuer it was requested for, the scope
+/// and the audience. The scope values are sorted, so their order does not matter.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    account: Option<String>,
+    issuer: Option<Url>,
+    scope: Option<Vec<String>>,
+    audience: Option<String>,
+}
+
+impl CacheKey {
+    /// Creates the key of the token requested by the `request`.
+    pub fn from_request(request: &AccessTokenRequest) -> Self {
+        let scope = request.scope().map(|scope| {
+            let mut scope: Vec<String> = scope.split_whitespace().map(String::from).collect();
+            scope.sort();
+            scope.dedup();
+            scope
+        });
+        Self {
+            account: request.account().map(String::from),
+            issuer: request.issuer().cloned(),
+            scope,
+            audience: request.audience().map(String::from),
+        }
+    }
+}
+
+/// Cache settings and entries shared by the sync and the async [`CachedAgent`].
+#[derive(Debug)]
+pub(crate) struct TokenCache<S> {
+    min_validity: Duration,
+    entries: Mutex<HashMap<CacheKey, S>>,
+}
+
+impl<S> TokenCache<S> {
+    pub(crate) fn new() -> Self {
+        Self {
+            min_validity: DEFAULT_MIN_VALIDITY,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn set_min_validity(&mut self, min_validity: Duration) {
+        self.min_validity = min_validity;
+    }
+
+    /// Returns the validity the token must have to be served for the `request`, which is the
+    /// longer one of the configured minimum and the `min_valid_period` of the request. The
+    /// `request` is updated to ask the agent for a token valid at least that long.
+    pub(crate) fn required_validity(&self, request: &mut AccessTokenRequest) -> Duration {
+        let requested = Duration::from_secs(request.min_valid_period().unwrap_or_default());
+        let validity = requested.max(self.min_validity);
+        request.set_min_valid_period(validity.as_secs());
+        validity
+    }
+
+    pub(crate) fn entries(&self) -> MutexGuard<'_, HashMap<CacheKey, S>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Checks whether the cached `response` stays valid for at least the `validity`.
+pub(crate) fn is_fresh(response: &AccessTokenResponse, validity: Duration) -> bool {
+    let remaining = *response.expires_at() - Utc::now();
+    remaining
+        .to_std()
+        .is_ok_and(|remaining| remaining >= validity)
+}
+
+/// Wrapper of a [`TokenProvider`], by default the [`Agent`], caching the access tokens.
+///
+/// A cached token is served until it would expire within the minimum validity, see the
+/// [`CachedAgent::min_validity`]. The `min_valid_period` of a request is honored as well, the
+/// longer one applies. Mytokens are not cached. The `CachedAgent` can be shared between threads.
+///
+/// # Examples
+/// ```ignore
+/// let agent = CachedAgent::new(Agent::new()?).min_validity(Duration::from_secs(300));
+/// let first = agent.get_access_token("profile_shortname")?;
+/// // served from the cache
+/// let second = agent.get_access_token("profile_shortname")?;
+/// ```
+#[derive(Debug)]
+pub struct CachedAgent<P = Agent> {
+    provider: P,
+    cache: TokenCache<AccessTokenResponse>,
+}
+
+impl<P: TokenProvider> CachedAgent<P> {
+    /// Creates a new `CachedAgent` with an empty cache and the [`DEFAULT_MIN_VALIDITY`].
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider,
+            cache: TokenCache::new(),
+        }
+    }
+    /// Sets the minimum time the cached token must stay valid to be served.
+    pub fn min_validity(mut self, min_validity: Duration) -> Self {
+        self.cache.set_min_validity(min_validity);
+        self
+    }
+
+    /// Returns the wrapped provider.
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// Attempts to get the access token of the account, from the cache if possible.
+    /// # Errors
+    /// The same as the [`TokenProvider::fetch_access_token`] of the wrapped provider.
+    pub fn get_access_token(&self, account_shortname: &str) -> AgentResult<Token> {
+        self.fetch_access_token(account_shortname)
+    }
+
+    /// Attempts to get the full access token response of the account, from the cache if
+    /// possible.
+    /// # Errors
+    /// The same as the [`TokenProvider::fetch_access_token_response`] of the wrapped provider.
+    pub fn get_access_token_full(
+        &self,
+        account_shortname: &str,
+    ) -> AgentResult<AccessTokenResponse> {
+        self.fetch_access_token_response(AccessTokenRequest::basic(account_shortname))
+    }
+
+    /// Removes the token requested by the `request` from the cache, e.g. after the token was
+    /// rejected.
+    pub fn invalidate(&self, request: &AccessTokenRequest) {
+        self.cache
+            .entries()
+            .remove(&CacheKey::from_request(request));
+    }
+
+    /// Removes every token from the cache.
+    pub fn clear(&self) {
+        self.cache.entries().clear();
+    }
+}
+
+impl<P: TokenProvider> TokenProvider for CachedAgent<P> {
+    fn fetch_access_token_response(
+        &self,
+        mut request: AccessTokenRequest,
+    ) -> AgentResult<AccessTokenResponse> {
+        let key = CacheKey::from_request(&request);
+        let validity = self.cache.required_validity(&mut request);
+        if let Some(response) = self.cache.entries().get(&key)
+            && is_fresh(response, validity)
+        {
+            return Ok(response.clone());
+        }
+        let response = self.provider.fetch_access_token_response(request)?;
+        self.cache.entries().insert(key, response.clone());
+        Ok(response)
+    }
+
+    fn fetch_mytoken(&self, account_shortname: &str) -> AgentResult<Token> {
+        self.provider.fetch_mytoken(account_shortname)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Provider returning tokens numbered by the call, valid for the `lifetime`.
+    struct CountingProvider {
+        calls: AtomicUsize,
+        lifetime: chrono::Duration,
+    }
+
+    impl TokenProvider for CountingProvider {
+        fn fetch_access_token_response(
+            &self,
+            request: AccessTokenRequest,
+        ) -> AgentResult<AccessTokenResponse> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(AccessTokenResponse::new(
+                Token(format!(
+                    "token-{}-{}",
+                    call,
+                    request.min_valid_period().unwrap()
+                )),
+                Url::parse("https://issuer.example").unwrap(),
+                Utc::now() + self.lifetime,
+            ))
+        }
+
+        fn fetch_mytoken(&self, _account_shortname: &str) -> AgentResult<Token> {
+            Err("The provider has no mytoken!".into())
+        }
+    }
+
+    fn cached_agent(lifetime: chrono::Duration) -> CachedAgent<CountingProvider> {
+        CachedAgent::new(CountingProvider {
+            calls: AtomicUsize::new(0),
+            lifetime,
+        })
+    }
+
+    fn token(agent: &CachedAgent<CountingProvider>, request: AccessTokenRequest) -> String {
+        let response = agent.fetch_access_token_response(request).unwrap();
+        response.access_token().secret().to_string()
+    }
+
+    #[test]
+    fn serves_cached_token_until_min_validity() {
+        let agent = cached_agent(chrono::Duration::minutes(10));
+        assert_eq!(
+            agent.get_access_token("profile").unwrap().secret(),
+            "token-0-60"
+        );
+        assert_eq!(
+            agent.get_access_token("profile").unwrap().secret(),
+            "token-0-60"
+        );
+
+        let request = AccessTokenRequest::builder()
+            .account("profile")
+            .min_valid_period(3600)
+            .build()
+            .unwrap();
+        assert_eq!(token(&agent, request), "token-1-3600");
+
+        let agent =
+            cached_agent(chrono::Duration::minutes(10)).min_validity(Duration::from_secs(900));
+        assert_eq!(
+            agent.get_access_token("profile").unwrap().secret(),
+            "token-0-900"
+        );
+        assert_eq!(
+            agent.get_access_token("profile").unwrap().secret(),
+            "token-1-900"
+        );
+    }
+
+    #[test]
+    fn keys_tokens_by_scope_and_audience() {
+        let agent = cached_agent(chrono::Duration::hours(1));
+        let request = |scopes: &[&str], audience: &str| {
+            scopes
+                .iter()
+                .fold(
+                    AccessTokenRequest::builder().account("profile"),
+                    |builder, scope| builder.add_scope(scope),
+                )
+                .audience(audience)
+                .build()
+                .unwrap()
+        };
+        assert_eq!(
+            token(&agent, request(&["openid", "email"], "a")),
+            "token-0-60"
+        );
+        assert_eq!(
+            token(&agent, request(&["email", "openid"], "a")),
+            "token-0-60"
+        );
+        assert_eq!(token(&agent, request(&["openid"], "a")), "token-1-60");
+        assert_eq!(
+            token(&agent, request(&["openid", "email"], "b")),
+            "token-2-60"
+        );
+
+        agent.invalidate(&request(&["email", "openid"], "a"));
+        assert_eq!(
+            token(&agent, request(&["openid", "email"], "a")),
+            "token-3-60"
+        );
+    }
+}
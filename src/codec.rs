@@ -0,0 +1,449 @@
+use serde::de::IgnoredAny;
+use std::io;
+
+use crate::errors::AgentError;
+use crate::responses::{BasicResponse, OIDCAgentResponse, Status};
+use crate::{AgentResult, Error, Request, Response};
+
+/// Size of the chunks the agent responses are read in.
+pub(crate) const READ_CHUNK: usize = 4096;
+
+/// Default maximum size of a single agent message, see the
+/// [`MessageDecoder::with_max_message_size`].
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// Attempts to encode the `request` into the message sent to the agent.
+/// # Errors
+/// The method returns an coresponding [`Error`] if:
+/// - the request cannot be serialized.
+pub fn encode_request<T: Request>(request: &T) -> AgentResult<Vec<u8>> {
+    Ok(serde_json::to_vec(request)?)
+}
+
+/// Attempts to decode a single agent `message` as the response `R`.
+/// # Errors
+/// The method returns an coresponding [`Error`] if:
+/// - the message cannot be deserialized,
+/// - the oidc-agnet returned an error,
+/// - the oidc-agent returned a status other than `success`, see [`Status`]. The `accepted`
+///   status is only expected by the responses of the flows, see the
+///   [`Response::ACCEPTS_ACCEPTED`].
+pub fn decode_response<R: Response>(message: &[u8]) -> AgentResult<R> {
+    let resp: OIDCAgentResponse = serde_json::from_slice(message)?;
+    match resp.status() {
+        Status::SUCCESS => {
+            let r: R = serde_json::from_slice(message)?;
+            Ok(r)
+        }
+        Status::ACCEPTED if R::ACCEPTS_ACCEPTED => {
+            let r: R = serde_json::from_slice(message)?;
+            Ok(r)
+        }
+        Status::ACCEPTED => {
+            let r: serde_json::Value = serde_json::from_slice(message)?;
+            Err(Error::UnexpectedStatusError(resp.status().clone(), r))
+        }
+        Status::FAILURE => {
+            let r: AgentError = serde_json::from_slice(message)?;
+            Err(r.into())
+        }
+        Status::NOTFOUND => {
+            let r: BasicResponse = serde_json::from_slice(message)?;
+            Err(Error::NotFoundError(r.info().cloned()))
+        }
+        Status::FOUNDBUTDONE => {
+            let r: BasicResponse = serde_json::from_slice(message)?;
+            Err(Error::FoundButDoneError(r.info().cloned()))
+        }
+        Status::UNKNOWN(_) => {
+            let r: serde_json::Value = serde_json::from_slice(message)?;
+            Err(Error::UnknownStatusError(resp.status().clone(), r))
+        }
+    }
+}
+
+/// Decoder splitting the bytes received from the agent into complete messages.
+///
+/// The agent does not delimit its messages, so a message is complete once it parses as a JSON
+/// value. Bytes following the message are kept for the next one. The received bytes are scanned
+/// only once, the message is parsed when its end was found. The decoder does no IO, the caller
+/// feeds it with whatever its transport received.
+///
+/// # Examples
+/// ```ignore
+/// let mut decoder = MessageDecoder::default();
+/// stream.write_all(&encode_request(&CheckRequest::new())?)?;
+/// let message = loop {
+///     if let Some(message) = decoder.next_message()? {
+///         break message;
+///     }
+///     let read = stream.read(&mut chunk)?;
+///     decoder.feed(&chunk[..read]);
+/// };
+/// let status: StatusResponse = decode_response(&message)?;
+/// ```
+#[derive(Debug)]
+pub struct MessageDecoder {
+    buffer: Vec<u8>,
+    max_message_size: usize,
+    /// Number of the buffered bytes already scanned for the end of the message.
+    scanned: usize,
+    /// Nesting depth of the objects and arrays at the end of the scanned bytes.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Default for MessageDecoder {
+    fn default() -> Self {
+        Self::with_max_message_size(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+}
+
+impl MessageDecoder {
+    /// Creates a new decoder accepting messages of at most `max_message_size` bytes.
+    pub fn with_max_message_size(max_message_size: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            max_message_size,
+            scanned: 0,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+        }
+    }
+
+    /// Appends the received `data` to the decoder.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Checks whether the decoder holds only whitespace, i.e. no part of the next message.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.iter().all(u8::is_ascii_whitespace)
+    }
+
+    /// Removes the first complete message from the decoder. Returns `None` if more data is needed.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - the received data is not valid JSON,
+    /// - the message is longer than the maximum message size.
+    ///
+    /// The buffered data is discarded then, the message boundaries cannot be found anymore.
+    pub fn next_message(&mut self) -> AgentResult<Option<Vec<u8>>> {
+        let result = match self.scan() {
+            Scan::Complete(end) if end > self.max_message_size => Err(self.too_large()),
+            Scan::Complete(end) => {
+                match serde_json::from_slice::<IgnoredAny>(&self.buffer[..end]) {
+                    Ok(_) => {
+                        self.reset();
+                        return Ok(Some(self.buffer.drain(..end).collect()));
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Scan::Incomplete if self.buffer.len() > self.max_message_size => Err(self.too_large()),
+            Scan::Incomplete => return Ok(None),
+            Scan::NotContainer => return self.next_scalar(),
+        };
+        self.buffer.clear();
+        self.reset();
+        result
+    }
+
+    /// Scans the bytes received since the last call for the end of the message.
+    fn scan(&mut self) -> Scan {
+        while let Some(&byte) = self.buffer.get(self.scanned) {
+            self.scanned += 1;
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => {
+                        self.in_string = false;
+                        if self.depth == 0 {
+                            return Scan::Complete(self.scanned);
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' if self.depth > 1 => self.depth -= 1,
+                b'}' | b']' => return Scan::Complete(self.scanned),
+                _ if self.depth > 0 || byte.is_ascii_whitespace() => {}
+                _ => return Scan::NotContainer,
+            }
+        }
+        Scan::Incomplete
+    }
+
+    /// Parses the message that is neither an object, an array nor a string. The end of a number
+    /// is only known from the byte following it, so the whole buffer is parsed.
+    fn next_scalar(&mut self) -> AgentResult<Option<Vec<u8>>> {
+        let mut messages =
+            serde_json::Deserializer::from_slice(&self.buffer).into_iter::<IgnoredAny>();
+        let result = match messages.next() {
+            Some(Ok(_)) if messages.byte_offset() > self.max_message_size => Err(self.too_large()),
+            Some(Ok(_)) => {
+                let end = messages.byte_offset();
+                self.reset();
+                return Ok(Some(self.buffer.drain(..end).collect()));
+            }
+            Some(Err(e)) if e.is_eof() => {
+                if self.buffer.len() <= self.max_message_size {
+                    self.reset();
+                    return Ok(None);
+                }
+                Err(self.too_large())
+            }
+            Some(Err(e)) => Err(e.into()),
+            None => {
+                self.reset();
+                return Ok(None);
+            }
+        };
+        self.buffer.clear();
+        self.reset();
+        result
+    }
+
+    fn reset(&mut self) {
+        self.scanned = 0;
+        self.depth = 0;
+        self.in_string = false;
+        self.escaped = false;
+    }
+
+    fn too_large(&self) -> Error {
+        Error::IoError(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the agent response exceeds the maximum message size of {} bytes",
+                self.max_message_size
+            ),
+        ))
+    }
+
+    /// Returns the error for the stream closed before the next message was complete.
+    pub(crate) fn closed_error(&self) -> io::Error {
+        let message = if self.is_empty() {
+            "the agent closed the connection without a response"
+        } else {
+            "the agent closed the connection in the middle of the response"
+        };
+        io::Error::new(io::ErrorKind::UnexpectedEof, message)
+    }
+}
+
+/// Result of scanning the buffered bytes for the end of the message.
+enum Scan {
+    /// The message ends before the given offset.
+    Complete(usize),
+    /// The end of the message was not received yet.
+    Incomplete,
+    /// The message is not an object, an array or a string.
+    NotContainer,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::AccessTokenRequest;
+    use crate::responses::{AccessTokenResponse, GenResponse};
+
+    fn messages(decoder: &mut MessageDecoder) -> Vec<String> {
+        std::iter::from_fn(|| decoder.next_message().unwrap())
+            .map(|message| String::from_utf8(message).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn decodes_message_split_across_reads() {
+        let message = r#"{"status":"success","access_token":"token"}"#;
+        let mut decoder = MessageDecoder::default();
+        for chunk in message.as_bytes().chunks(5) {
+            assert!(decoder.next_message().unwrap().is_none());
+            decoder.feed(chunk);
+        }
+        assert_eq!(messages(&mut decoder), vec![message]);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn decodes_split_inside_string_and_escape() {
+        let message = r#"{"status":"failure","error":"a \"quoted\" }{ brace"}"#;
+        let (first, second) = message.split_at(message.find('\\').unwrap() + 1);
+        let mut decoder = MessageDecoder::default();
+        decoder.feed(first.as_bytes());
+        assert!(decoder.next_message().unwrap().is_none());
+        decoder.feed(second.as_bytes());
+        assert_eq!(messages(&mut decoder), vec![message]);
+    }
+
+    #[test]
+    fn decodes_several_messages_in_one_buffer() {
+        let mut decoder = MessageDecoder::default();
+        decoder.feed(br#"{"status":"success"} {"status":"failure","error":"e"}{"status":"#);
+        assert_eq!(
+            messages(&mut decoder),
+            vec![
+                r#"{"status":"success"}"#,
+                r#" {"status":"failure","error":"e"}"#
+            ]
+        );
+        assert!(!decoder.is_empty());
+        decoder.feed(br#""success"}"#);
+        assert_eq!(messages(&mut decoder), vec![r#"{"status":"success"}"#]);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn reports_connection_closed_mid_message() {
+        let mut decoder = MessageDecoder::default();
+        assert!(
+            decoder
+                .closed_error()
+                .to_string()
+                .contains("without a response")
+        );
+        decoder.feed(br#"{"status":"succ"#);
+        assert!(decoder.next_message().unwrap().is_none());
+        let error = decoder.closed_error();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(error.to_string().contains("in the middle"));
+    }
+
+    #[test]
+    fn discards_invalid_json() {
+        let mut decoder = MessageDecoder::default();
+        decoder.feed(b"{bad json}");
+        assert!(matches!(decoder.next_message(), Err(Error::SerdeError(_))));
+        assert!(decoder.is_empty());
+        decoder.feed(br#"{"status":"success"}"#);
+        assert_eq!(messages(&mut decoder), vec![r#"{"status":"success"}"#]);
+    }
+
+    #[test]
+    fn scans_received_data_once() {
+        let message = r#"{"status":"success","info":["a","b\\\"c"]}"#;
+        let mut decoder = MessageDecoder::default();
+        for byte in message.as_bytes() {
+            assert!(decoder.next_message().unwrap().is_none());
+            assert_eq!(decoder.scanned, decoder.buffer.len());
+            decoder.feed(&[*byte]);
+        }
+        assert_eq!(messages(&mut decoder), vec![message]);
+        decoder.feed(b" 42 \"text\"");
+        assert_eq!(messages(&mut decoder), vec![" 42", r#" "text""#]);
+    }
+
+    #[test]
+    fn rejects_oversized_messages() {
+        let mut decoder = MessageDecoder::with_max_message_size(32);
+        decoder.feed(br#"{"status":"success","access_token":""#);
+        let error = decoder.next_message().unwrap_err();
+        assert!(matches!(&error, Error::IoError(e) if e.kind() == io::ErrorKind::InvalidData));
+        assert!(error.to_string().contains("32 bytes"));
+        assert!(decoder.is_empty());
+
+        decoder.feed(br#"{"status":"success","info":"0123456789"}"#);
+        assert!(decoder.next_message().is_err());
+        assert!(decoder.is_empty());
+
+        decoder.feed(br#"{"status":"success"}"#);
+        assert_eq!(messages(&mut decoder), vec![r#"{"status":"success"}"#]);
+        decoder.feed(b"1234567890123456789012345678901234567890");
+        assert!(decoder.next_message().is_err());
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn discards_mismatched_brackets() {
+        let mut decoder = MessageDecoder::default();
+        decoder.feed(br#"{"status":"success"]{"status":"success"}"#);
+        assert!(matches!(decoder.next_message(), Err(Error::SerdeError(_))));
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn encodes_request() {
+        let request = AccessTokenRequest::builder()
+            .account("profile")
+            .min_valid_period(60)
+            .build()
+            .unwrap();
+        let message: serde_json::Value =
+            serde_json::from_slice(&encode_request(&request).unwrap()).unwrap();
+        assert_eq!(
+            message,
+            serde_json::json!({"request": "access_token", "account": "profile", "min_valid_period": 60})
+        );
+    }
+
+    #[test]
+    fn decodes_success() {
+        let response: AccessTokenResponse = decode_response(
+            br#"{"status":"success","access_token":"token","issuer":"https://issuer.example/","expires_at":1700000000}"#,
+        )
+        .unwrap();
+        assert_eq!(response.access_token().secret(), "token");
+        assert_eq!(response.expires_at().timestamp(), 1_700_000_000);
+    }
+
+    #[test]
+    fn decodes_failures() {
+        let failure =
+            decode_response::<BasicResponse>(br#"{"status":"failure","error":"Agent locked"}"#);
+        assert!(matches!(failure, Err(Error::AgentError(_))));
+        let reauthentication = decode_response::<BasicResponse>(
+            br#"{"status":"failure","error":"Could not get access token","info":"invalid_grant"}"#,
+        );
+        assert!(matches!(
+            reauthentication,
+            Err(Error::ReauthenticationRequired(_))
+        ));
+        let not_found =
+            decode_response::<GenResponse>(br#"{"status":"NotFound","info":"no state"}"#);
+        assert!(matches!(not_found, Err(Error::NotFoundError(Some(info))) if info == "no state"));
+        let done = decode_response::<GenResponse>(br#"{"status":"FoundButDone"}"#);
+        assert!(matches!(done, Err(Error::FoundButDoneError(None))));
+    }
+
+    #[test]
+    fn decodes_unknown_status() {
+        let response = decode_response::<BasicResponse>(br#"{"status":"later","info":"x"}"#);
+        assert!(matches!(
+            response,
+            Err(Error::UnknownStatusError(Status::UNKNOWN(status), value))
+                if status == "later" && value["info"] == "x"
+        ));
+    }
+
+    #[test]
+    fn accepts_accepted_status_only_for_flows() {
+        let message = br#"{"status":"accepted","uri":"https://issuer.example/auth","state":"s"}"#;
+        let response: GenResponse = decode_response(message).unwrap();
+        assert_eq!(response.status(), &Status::ACCEPTED);
+        assert_eq!(response.state().map(String::as_str), Some("s"));
+        let response = decode_response::<AccessTokenResponse>(message);
+        assert!(matches!(
+            response,
+            Err(Error::UnexpectedStatusError(Status::ACCEPTED, _))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_responses() {
+        let missing_status = decode_response::<BasicResponse>(br#"{"error":"e"}"#);
+        assert!(matches!(missing_status, Err(Error::SerdeError(_))));
+        let missing_field =
+            decode_response::<AccessTokenResponse>(br#"{"status":"success","issuer":"x"}"#);
+        assert!(matches!(missing_field, Err(Error::SerdeError(_))));
+        let invalid = decode_response::<BasicResponse>(b"{bad json}");
+        assert!(matches!(invalid, Err(Error::SerdeError(_))));
+    }
+}
@@ -0,0 +1,78 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+
+use crate::builder::AgentOptions;
+use crate::codec::{self, MessageDecoder, READ_CHUNK};
+use crate::requests::GenericRequest;
+use crate::{AgentResult, Request};
+
+/// Persistent connection to the agent sending many requests over one stream.
+///
+/// Every request sent by the [`crate::Agent`] opens a new connection. The `Connection` is opened
+/// once with the [`crate::Agent::connection`] and reused, which saves the connect and the peer
+/// verification per request. Requests are sent one by one, each response is read before the next
+/// request is written.
+///
+/// # Examples
+/// ```ignore
+/// let mut connection = agent.connection()?;
+/// for account in ["work", "personal"] {
+///     let response = connection.send_request(AccessTokenRequest::basic(account))?;
+///     println!("{}", response.access_token().secret());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Connection<S = UnixStream> {
+    stream: S,
+    decoder: MessageDecoder,
+    options: AgentOptions,
+}
+
+impl<S: Read + Write> Connection<S> {
+    pub(crate) fn new(stream: S, options: AgentOptions) -> Self {
+        Self {
+            stream,
+            decoder: MessageDecoder::default(),
+            options,
+        }
+    }
+
+    /// Consumes the [`Request`], sends it over the connection and attempts to retrives the
+    /// [`crate::Response`].
+    /// # Errors
+    /// The same as [`crate::Agent::send_request`]. After an IO error the connection should not be
+    /// used anymore.
+    pub fn send_request<T>(&mut self, mut request: T) -> AgentResult<T::SuccessResponse>
+    where
+        T: Request,
+    {
+        self.options.apply(&mut request);
+        self.stream.write_all(&codec::encode_request(&request)?)?;
+        let message = self.read_message()?;
+        codec::decode_response(&message)
+    }
+
+    /// The same as [`crate::Agent::send_raw`], but sends the request over the connection.
+    /// # Errors
+    /// The same as [`Connection::send_request`]. Additionally, if the `request` is not a JSON
+    /// object with a string `request` field.
+    pub fn send_raw(&mut self, request: serde_json::Value) -> AgentResult<serde_json::Value> {
+        let request = GenericRequest::<serde_json::Value>::new(request)?;
+        self.send_request(request)
+    }
+
+    /// Reads exactly one complete message from the stream.
+    fn read_message(&mut self) -> AgentResult<Vec<u8>> {
+        let mut chunk = [0; READ_CHUNK];
+        loop {
+            if let Some(message) = self.decoder.next_message()? {
+                return Ok(message);
+            }
+            let read = self.stream.read(&mut chunk)?;
+            if read == 0 {
+                return Err(self.decoder.closed_error().into());
+            }
+            self.decoder.feed(&chunk[..read]);
+        }
+    }
+}
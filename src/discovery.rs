@@ -0,0 +1,326 @@
+use std::env;
+use std::fmt::Display;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+
+use crate::builder::AgentOptions;
+use crate::peer::PeerMismatch;
+use crate::socket::SocketAddress;
+use crate::transport::{Transport, UnixTransport};
+use crate::Error;
+
+/// Place the agent socket path was looked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketSource {
+    /// The environment variable with the given name, `OIDC_SOCK` by default.
+    EnvVar(String),
+    /// The `oidc-agent-service` socket in `/tmp/oidc-agent-service-$UID/oidc-agent.sock`.
+    ServiceTmp,
+    /// The `oidc-agent-service` socket in
+    /// `$XDG_RUNTIME_DIR/oidc-agent-service-$UID/oidc-agent.sock`.
+    XdgRuntimeDir,
+}
+
+impl Display for SocketSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EnvVar(name) => write!(f, "${}", name),
+            Self::ServiceTmp => write!(f, "oidc-agent-service tmp dir"),
+            Self::XdgRuntimeDir => write!(f, "$XDG_RUNTIME_DIR"),
+        }
+    }
+}
+
+/// Reason why a socket candidate was rejected.
+#[derive(Debug)]
+pub enum Rejection {
+    /// The environment variable is not set or is not valid unicode.
+    EnvVarNotSet(env::VarError),
+    /// Nothing exists at the candidate path.
+    Missing,
+    /// The candidate path exists, but is not a socket.
+    NotASocket,
+    /// The candidate is a socket, but connecting to it failed, e.g. the agent is not running
+    /// anymore.
+    ConnectFailed(io::Error),
+    /// The candidate accepted a connection, but failed the peer verification, see
+    /// [`crate::peer`].
+    PeerVerificationFailed(PeerMismatch),
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EnvVarNotSet(e) => write!(f, "{}", e),
+            Self::Missing => write!(f, "no such file"),
+            Self::NotASocket => write!(f, "not a socket"),
+            Self::ConnectFailed(e) => write!(f, "connection failed: {}", e),
+            Self::PeerVerificationFailed(e) => write!(f, "peer verification failed: {}", e),
+        }
+    }
+}
+
+/// Single candidate checked during the discovery.
+#[derive(Debug)]
+pub struct DiscoveryAttempt {
+    source: SocketSource,
+    address: Option<SocketAddress>,
+    rejection: Option<Rejection>,
+}
+
+impl DiscoveryAttempt {
+    pub fn source(&self) -> &SocketSource {
+        &self.source
+    }
+    /// Returns the file system path of the candidate, `None` for abstract sockets.
+    pub fn path(&self) -> Option<&Path> {
+        self.address.as_ref().and_then(SocketAddress::as_path)
+    }
+    pub fn address(&self) -> Option<&SocketAddress> {
+        self.address.as_ref()
+    }
+    /// Returns the reason the candidate was rejected, or `None` if it was selected.
+    pub fn rejection(&self) -> Option<&Rejection> {
+        self.rejection.as_ref()
+    }
+}
+
+/// Report of the socket discovery listing every candidate that was tried, in order.
+#[derive(Debug, Default)]
+pub struct DiscoveryReport {
+    attempts: Vec<DiscoveryAttempt>,
+}
+
+impl DiscoveryReport {
+    pub fn attempts(&self) -> &[DiscoveryAttempt] {
+        &self.attempts
+    }
+    /// Returns the address of the selected candidate, if any was accepted.
+    pub fn selected(&self) -> Option<&SocketAddress> {
+        self.attempts
+            .iter()
+            .find(|a| a.rejection.is_none())
+            .and_then(|a| a.address())
+    }
+}
+
+impl DiscoveryReport {
+    /// Turns the report without a selected candidate into the error. The rejection of the set
+    /// environment variable is returned as it is, e.g. the failed peer verification.
+    pub(crate) fn into_error(mut self) -> Error {
+        let env_rejected = self.attempts.last().is_some_and(|a| {
+            matches!(a.source, SocketSource::EnvVar(_))
+                && !matches!(a.rejection, Some(Rejection::EnvVarNotSet(_)))
+        });
+        if env_rejected && let Some(attempt) = self.attempts.pop() {
+            match attempt.rejection {
+                Some(Rejection::PeerVerificationFailed(e)) => {
+                    return Error::PeerVerificationError(e);
+                }
+                Some(Rejection::ConnectFailed(e)) => return Error::IoError(e),
+                _ => self.attempts.push(attempt),
+            }
+        }
+        Error::DiscoveryError(self)
+    }
+}
+
+impl Display for DiscoveryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let attempts: Vec<String> = self
+            .attempts
+            .iter()
+            .map(|a| {
+                let path = a
+                    .address
+                    .as_ref()
+                    .map_or_else(|| "-".to_string(), ToString::to_string);
+                match &a.rejection {
+                    Some(rejection) => format!("{} ({}): {}", a.source, path, rejection),
+                    None => format!("{} ({}): selected", a.source, path),
+                }
+            })
+            .collect();
+        write!(f, "[{}]", attempts.join(", "))
+    }
+}
+
+/// Looks for the agent socket. The `env_var` environment variable is checked first. Only if it
+/// is not set, the default `oidc-agent-service` locations are tried, a set variable pointing to a
+/// dead or untrusted socket is never bypassed. The first candidate that is an existing socket is
+/// selected. If `check_connection` is set, the candidate must also accept a connection and pass
+/// the peer verification. No connect timeout is set, use the
+/// [`crate::AgentBuilder::discover_socket`] to limit the time of the check.
+pub fn discover(env_var: &str, check_connection: bool) -> DiscoveryReport {
+    discover_with(
+        env_var,
+        check_connection.then(AgentOptions::default).as_ref(),
+    )
+}
+
+/// Looks for the agent socket like [`discover`]. If `check_connection` is set, the candidate is
+/// connected to with its timeout and peer verification settings.
+pub(crate) fn discover_with(
+    env_var: &str,
+    check_connection: Option<&AgentOptions>,
+) -> DiscoveryReport {
+    discover_from(
+        SocketSource::EnvVar(env_var.to_string()),
+        env_candidate(env_var),
+        check_connection,
+    )
+}
+
+fn discover_from(
+    env_source: SocketSource,
+    env_candidate: Result<SocketAddress, Rejection>,
+    check_connection: Option<&AgentOptions>,
+) -> DiscoveryReport {
+    let mut report = DiscoveryReport::default();
+    let candidates = [
+        (env_source, env_candidate),
+        (
+            SocketSource::ServiceTmp,
+            Ok(SocketAddress::Path(service_tmp_candidate())),
+        ),
+        (SocketSource::XdgRuntimeDir, xdg_candidate()),
+    ];
+    for (source, candidate) in candidates {
+        let (address, rejection) = match candidate {
+            Ok(address) => {
+                let rejection = check(&address, check_connection);
+                (Some(address), rejection)
+            }
+            Err(rejection) => (None, Some(rejection)),
+        };
+        // Falling back from a set environment variable could pick another user's agent.
+        let done = match &rejection {
+            None => true,
+            Some(Rejection::EnvVarNotSet(_)) => false,
+            Some(_) => matches!(source, SocketSource::EnvVar(_)),
+        };
+        report.attempts.push(DiscoveryAttempt {
+            source,
+            address,
+            rejection,
+        });
+        if done {
+            break;
+        }
+    }
+    report
+}
+
+fn env_candidate(env_var: &str) -> Result<SocketAddress, Rejection> {
+    env::var(env_var)
+        .map(|value| SocketAddress::from(value.as_str()))
+        .map_err(Rejection::EnvVarNotSet)
+}
+
+fn service_tmp_candidate() -> PathBuf {
+    PathBuf::from(format!("/tmp/oidc-agent-service-{}", current_uid())).join("oidc-agent.sock")
+}
+
+fn xdg_candidate() -> Result<SocketAddress, Rejection> {
+    let runtime_dir = env::var("XDG_RUNTIME_DIR").map_err(Rejection::EnvVarNotSet)?;
+    Ok(SocketAddress::Path(
+        Path::new(&runtime_dir)
+            .join(format!("oidc-agent-service-{}", current_uid()))
+            .join("oidc-agent.sock"),
+    ))
+}
+
+/// Checks the candidate. Abstract sockets have no file to inspect, so only the connection can be
+/// checked for them.
+fn check(address: &SocketAddress, check_connection: Option<&AgentOptions>) -> Option<Rejection> {
+    let meta = address.as_path().map(Path::metadata);
+    match (meta, check_connection) {
+        (Some(Err(_)), _) => Some(Rejection::Missing),
+        (Some(Ok(meta)), _) if !meta.file_type().is_socket() => Some(Rejection::NotASocket),
+        (_, Some(options)) => {
+            let transport = UnixTransport::with_options(address.clone(), options.clone());
+            match transport.connect() {
+                Ok(_) => None,
+                Err(Error::PeerVerificationError(e)) => Some(Rejection::PeerVerificationFailed(e)),
+                Err(Error::IoError(e)) => Some(Rejection::ConnectFailed(e)),
+                Err(e) => Some(Rejection::ConnectFailed(io::Error::other(e.to_string()))),
+            }
+        }
+        (_, None) => None,
+    }
+}
+
+pub(crate) fn current_uid() -> u32 {
+    // SAFETY: getuid is always successful and has no side effects.
+    unsafe { libc::getuid() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::private_socket_dir;
+    use std::fs::{self, DirBuilder, Permissions};
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    fn discover_env(address: &Path, check_connection: bool) -> DiscoveryReport {
+        discover_from(
+            SocketSource::EnvVar("TEST_OIDC_SOCK".to_string()),
+            Ok(SocketAddress::Path(address.to_path_buf())),
+            check_connection.then(AgentOptions::default).as_ref(),
+        )
+    }
+
+    #[test]
+    fn falls_back_only_if_env_var_not_set() {
+        let report = discover_from(
+            SocketSource::EnvVar("TEST_OIDC_SOCK".to_string()),
+            Err(Rejection::EnvVarNotSet(env::VarError::NotPresent)),
+            None,
+        );
+        assert!(report.attempts().len() > 1);
+        assert_eq!(report.attempts()[1].source(), &SocketSource::ServiceTmp);
+
+        let dir = private_socket_dir().unwrap();
+        let report = discover_env(&dir.join("missing.sock"), false);
+        assert_eq!(report.attempts().len(), 1);
+        assert!(matches!(report.into_error(), Error::DiscoveryError(_)));
+
+        let file = dir.join("file");
+        fs::write(&file, "").unwrap();
+        let report = discover_env(&file, false);
+        assert_eq!(report.attempts().len(), 1);
+        assert!(matches!(
+            report.attempts()[0].rejection(),
+            Some(Rejection::NotASocket)
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn returns_env_socket_rejection() {
+        let dir = private_socket_dir().unwrap();
+        let dead = dir.join("dead.sock");
+        drop(UnixListener::bind(&dead).unwrap());
+        let report = discover_env(&dead, true);
+        assert_eq!(report.attempts().len(), 1);
+        assert!(matches!(report.into_error(), Error::IoError(_)));
+
+        let shared_dir = dir.join("shared");
+        DirBuilder::new().mode(0o755).create(&shared_dir).unwrap();
+        let shared = shared_dir.join("agent.sock");
+        let _listener = UnixListener::bind(&shared).unwrap();
+        fs::set_permissions(&shared, Permissions::from_mode(0o777)).unwrap();
+        let report = discover_env(&shared, true);
+        assert_eq!(report.attempts().len(), 1);
+        assert!(matches!(
+            report.into_error(),
+            Error::PeerVerificationError(PeerMismatch::SocketMode(0o777))
+        ));
+
+        let report = discover_env(&shared, false);
+        assert_eq!(report.selected(), Some(&SocketAddress::Path(shared)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
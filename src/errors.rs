@@ -1,15 +1,142 @@
+use crate::discovery::DiscoveryReport;
+use crate::peer::PeerMismatch;
+use crate::responses::Status;
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::env;
 use std::fmt::{Display, Formatter, Result};
 use std::io;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AgentErrorKind {
+    ///The requested account is not loaded into the agent.
+    AccountNotLoaded,
+    ///The user denied the confirmation of the request.
+    UserDenied,
+    ///The agent is locked and has to be unlocked first.
+    AgentLocked,
+    ///The agent could not reach the issuer.
+    IssuerUnreachable,
+    ///The agent did not understand the request.
+    InvalidRequest,
+    ///The refresh token expired or was revoked, the account has to be reauthenticated.
+    NeedsReauthentication,
+    ///Error not recognized by this crate.
+    Unknown,
+}
+
+impl AgentErrorKind {
+    /// Matches the messages of the agent and the issuer errors it forwards. Only whole phrases
+    /// are matched, e.g. "Permission denied" is not a denial by the user.
+    fn parse(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let mentions = |phrases: &[&str]| {
+            phrases.iter().any(|phrase| {
+                message.match_indices(phrase).any(|(start, _)| {
+                    !is_word(message[..start].chars().next_back())
+                        && !is_word(message[start + phrase.len()..].chars().next())
+                })
+            })
+        };
+        if mentions(&[
+            "invalid_grant",
+            "please reauthenticate",
+            "reauthentication required",
+            "must be reauthenticated",
+        ]) {
+            Self::NeedsReauthentication
+        } else if mentions(&["agent locked", "agent is locked"]) {
+            Self::AgentLocked
+        } else if mentions(&[
+            "account not loaded",
+            "account is not loaded",
+            "no account configured",
+            "account not found",
+        ]) {
+            Self::AccountNotLoaded
+        } else if mentions(&[
+            "user denied",
+            "denied by user",
+            "denied by the user",
+            "user declined",
+            "declined by user",
+            "declined by the user",
+            "not confirmed",
+            "access_denied",
+        ]) {
+            Self::UserDenied
+        } else if mentions(&[
+            "could not connect",
+            "couldn't connect",
+            "could not resolve",
+            "couldn't resolve",
+            "connection timed out",
+            "operation timed out",
+            "timeout was reached",
+            "network is unreachable",
+            "host is unreachable",
+            "no route to host",
+        ]) {
+            Self::IssuerUnreachable
+        } else if mentions(&[
+            "bad request",
+            "malformed",
+            "invalid request",
+            "unknown request",
+        ]) {
+            Self::InvalidRequest
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+impl Display for AgentErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AccountNotLoaded => write!(f, "account not loaded"),
+            Self::UserDenied => write!(f, "user denied"),
+            Self::AgentLocked => write!(f, "agent locked"),
+            Self::IssuerUnreachable => write!(f, "issuer unreachable"),
+            Self::InvalidRequest => write!(f, "invalid request"),
+            Self::NeedsReauthentication => write!(f, "needs reauthentication"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AgentError {
     error: String,
     info: Option<String>,
 }
 
+impl AgentError {
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+    pub fn info(&self) -> Option<&String> {
+        self.info.as_ref()
+    }
+    /// Parses the raw error message. If the message is not recognized,
+    /// [`AgentErrorKind::Unknown`] is returned.
+    pub fn kind(&self) -> AgentErrorKind {
+        match AgentErrorKind::parse(&self.error) {
+            AgentErrorKind::Unknown => self
+                .info
+                .as_deref()
+                .map_or(AgentErrorKind::Unknown, AgentErrorKind::parse),
+            kind => kind,
+        }
+    }
+    /// Checks whether the error was caused by an expired or revoked refresh token.
+    pub fn needs_reauthentication(&self) -> bool {
+        self.kind() == AgentErrorKind::NeedsReauthentication
+    }
+}
+
 impl Display for AgentError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(info) = &self.info {
@@ -23,12 +150,21 @@ impl Display for AgentError {
 impl std::error::Error for AgentError {}
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     EnvVarError(env::VarError),
+    DiscoveryError(DiscoveryReport),
     IoError(io::Error),
+    PeerVerificationError(PeerMismatch),
     SerdeError(serde_json::Error),
     AgentError(AgentError),
+    ReauthenticationRequired(AgentError),
+    RevocationFailed(AgentError),
     ParseError(url::ParseError),
+    NotFoundError(Option<String>),
+    FoundButDoneError(Option<String>),
+    UnknownStatusError(Status, serde_json::Value),
+    UnexpectedStatusError(Status, serde_json::Value),
     OtherError(String),
 }
 
@@ -36,23 +172,104 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
             Error::EnvVarError(e) => write!(f, "Environment variable error: {}", e),
+            Error::DiscoveryError(e) => write!(f, "No agent socket found: {}", e),
             Error::IoError(e) => write!(f, "IO error: {}", e),
+            Error::PeerVerificationError(e) => write!(f, "Peer verification error: {}", e),
             Error::SerdeError(e) => write!(f, "Serialization/Deserialization error: {}", e),
             Error::AgentError(e) => write!(f, "Agent error: {}", e),
+            Error::ReauthenticationRequired(e) => {
+                write!(f, "Reauthentication required: {}", e)
+            }
+            Error::RevocationFailed(e) => write!(f, "Token revocation failed: {}", e),
             Error::ParseError(e) => write!(f, "Parse error: Failed to parse URL: {}", e),
+            Error::NotFoundError(Some(e)) => write!(f, "Not found: {}", e),
+            Error::NotFoundError(None) => write!(f, "Not found"),
+            Error::FoundButDoneError(Some(e)) => write!(f, "Found but already done: {}", e),
+            Error::FoundButDoneError(None) => write!(f, "Found but already done"),
+            Error::UnknownStatusError(status, _) => {
+                write!(f, "Unknown agent response status: {}", status)
+            }
+            Error::UnexpectedStatusError(status, _) => {
+                write!(f, "Unexpected agent response status: {}", status)
+            }
             Error::OtherError(e) => write!(f, "Other error: {}", e),
         }
     }
 }
 
+impl Error {
+    /// Returns the kind of the error reported by the agent, if any.
+    pub fn agent_error_kind(&self) -> Option<AgentErrorKind> {
+        match self {
+            Error::AgentError(e)
+            | Error::ReauthenticationRequired(e)
+            | Error::RevocationFailed(e) => Some(e.kind()),
+            _ => None,
+        }
+    }
+
+    /// Checks whether sending the same request again may succeed without any user action,
+    /// e.g. after a transient socket failure or when the agent could not reach the issuer.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::IoError(e) => matches!(
+                e.kind(),
+                io::ErrorKind::TimedOut
+                    | io::ErrorKind::Interrupted
+                    | io::ErrorKind::WouldBlock
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::BrokenPipe
+            ),
+            Error::AgentError(e) | Error::RevocationFailed(e) => {
+                e.kind() == AgentErrorKind::IssuerUnreachable
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks whether the request can only succeed after the user acts, e.g. unlocks the agent,
+    /// loads the account, reauthenticates or finishes the authorization in the browser, see the
+    /// [`Error::NotFoundError`].
+    pub fn needs_user_action(&self) -> bool {
+        if let Error::NotFoundError(_) = self {
+            return true;
+        }
+        self.agent_error_kind().is_some_and(|kind| {
+            matches!(
+                kind,
+                AgentErrorKind::AccountNotLoaded
+                    | AgentErrorKind::UserDenied
+                    | AgentErrorKind::AgentLocked
+                    | AgentErrorKind::NeedsReauthentication
+            )
+        })
+    }
+
+    /// Marks the error reported by the agent to the delete request as the failed revocation.
+    pub(crate) fn into_revocation_error(self) -> Self {
+        match self {
+            Error::AgentError(e) | Error::ReauthenticationRequired(e) => Error::RevocationFailed(e),
+            e => e,
+        }
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::EnvVarError(e) => Some(e),
+            Error::DiscoveryError(_) => None,
             Error::IoError(e) => Some(e),
+            Error::PeerVerificationError(_) => None,
             Error::SerdeError(e) => Some(e),
             Error::AgentError(e) => Some(e),
+            Error::ReauthenticationRequired(e) => Some(e),
+            Error::RevocationFailed(e) => Some(e),
             Error::ParseError(e) => Some(e),
+            Error::NotFoundError(_) => None,
+            Error::FoundButDoneError(_) => None,
+            Error::UnknownStatusError(_, _) => None,
+            Error::UnexpectedStatusError(_, _) => None,
             Error::OtherError(_) => None,
         }
     }
@@ -78,7 +295,11 @@ impl From<serde_json::Error> for Error {
 
 impl From<AgentError> for Error {
     fn from(error: AgentError) -> Self {
-        Error::AgentError(error)
+        if error.needs_reauthentication() {
+            Error::ReauthenticationRequired(error)
+        } else {
+            Error::AgentError(error)
+        }
     }
 }
 
@@ -93,3 +314,147 @@ impl From<&'static str> for Error {
         Error::OtherError(error.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_agent_error_messages() {
+        let cases = [
+            ("account not loaded", AgentErrorKind::AccountNotLoaded),
+            (
+                "No account configured with that short name",
+                AgentErrorKind::AccountNotLoaded,
+            ),
+            ("Agent locked", AgentErrorKind::AgentLocked),
+            ("user denied", AgentErrorKind::UserDenied),
+            (
+                "Couldn't connect to server",
+                AgentErrorKind::IssuerUnreachable,
+            ),
+            (
+                "Couldn't resolve host name",
+                AgentErrorKind::IssuerUnreachable,
+            ),
+            ("Timeout was reached", AgentErrorKind::IssuerUnreachable),
+            (
+                "Bad request: unknown request type",
+                AgentErrorKind::InvalidRequest,
+            ),
+            ("Malformed request", AgentErrorKind::InvalidRequest),
+            (
+                "invalid_grant: Token is not active",
+                AgentErrorKind::NeedsReauthentication,
+            ),
+            (
+                "Please reauthenticate the account",
+                AgentErrorKind::NeedsReauthentication,
+            ),
+            ("access_denied", AgentErrorKind::UserDenied),
+            (
+                "Request not confirmed by the user",
+                AgentErrorKind::UserDenied,
+            ),
+            ("Operation timed out", AgentErrorKind::IssuerUnreachable),
+            ("Network is unreachable", AgentErrorKind::IssuerUnreachable),
+            ("Something went wrong", AgentErrorKind::Unknown),
+            // No false positives on words shared with unrelated errors.
+            ("Permission denied", AgentErrorKind::Unknown),
+            ("Access denied by policy", AgentErrorKind::Unknown),
+            ("access_denied_by_policy", AgentErrorKind::Unknown),
+            ("Failed to reauthenticate account", AgentErrorKind::Unknown),
+            (
+                "Could not reauthenticate: Couldn't connect to server",
+                AgentErrorKind::IssuerUnreachable,
+            ),
+            ("Shared library not loaded", AgentErrorKind::Unknown),
+            ("No accounts were changed", AgentErrorKind::Unknown),
+            ("Confirmation timed out", AgentErrorKind::Unknown),
+            ("Superuser denied", AgentErrorKind::Unknown),
+            ("Unreachable code entered", AgentErrorKind::Unknown),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(AgentErrorKind::parse(message), kind, "{message}");
+        }
+    }
+
+    #[test]
+    fn invalid_grant_takes_precedence() {
+        let cases = [
+            "invalid_grant: the account is not loaded",
+            "invalid_grant: request denied",
+            "invalid_grant: connection timed out",
+            "Bad request: invalid_grant",
+            "Agent locked: invalid_grant",
+        ];
+        for message in cases {
+            assert_eq!(
+                AgentErrorKind::parse(message),
+                AgentErrorKind::NeedsReauthentication,
+                "{message}"
+            );
+        }
+    }
+
+    #[test]
+    fn kind_falls_back_to_info() {
+        let error: AgentError = serde_json::from_str(
+            r#"{"error":"Could not get access token","info":"invalid_grant: Token is not active"}"#,
+        )
+        .unwrap();
+        assert_eq!(error.kind(), AgentErrorKind::NeedsReauthentication);
+        assert!(matches!(
+            Error::from(error),
+            Error::ReauthenticationRequired(_)
+        ));
+
+        let error: AgentError =
+            serde_json::from_str(r#"{"error":"Agent locked","info":"invalid_grant"}"#).unwrap();
+        assert_eq!(error.kind(), AgentErrorKind::AgentLocked);
+    }
+
+    #[test]
+    fn predicates_follow_kind() {
+        let error = |message: &str| {
+            Error::from(AgentError {
+                error: message.to_string(),
+                info: None,
+            })
+        };
+        assert!(error("Couldn't connect to server").is_retryable());
+        assert!(!error("Couldn't connect to server").needs_user_action());
+        assert!(error("Agent locked").needs_user_action());
+        assert!(error("invalid_grant").needs_user_action());
+        assert!(!error("Bad request").is_retryable());
+        assert!(!error("Bad request").needs_user_action());
+
+        let not_found = Error::NotFoundError(None);
+        assert!(!not_found.is_retryable());
+        assert!(not_found.needs_user_action());
+    }
+
+    #[test]
+    fn retries_transient_io_errors() {
+        let retryable = [
+            io::ErrorKind::TimedOut,
+            io::ErrorKind::Interrupted,
+            io::ErrorKind::WouldBlock,
+            io::ErrorKind::ConnectionReset,
+            io::ErrorKind::BrokenPipe,
+        ];
+        for kind in retryable {
+            assert!(Error::from(io::Error::from(kind)).is_retryable(), "{kind}");
+        }
+        let permanent = [
+            io::ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied,
+            io::ErrorKind::ConnectionRefused,
+            io::ErrorKind::InvalidData,
+            io::ErrorKind::UnexpectedEof,
+        ];
+        for kind in permanent {
+            assert!(!Error::from(io::Error::from(kind)).is_retryable(), "{kind}");
+        }
+    }
+}
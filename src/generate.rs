@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::errors::AgentError;
+use crate::requests::{CodeExchangeRequest, DeviceLookupRequest, StateLookupRequest};
+use crate::responses::{DeviceAuthorization, GenResponse};
+use crate::{AccountConfig, AgentResult, Error};
+use url::{Position, Url};
+
+/// Default polling interval defined by RFC 8628 if the issuer does not provide one.
+const DEFAULT_POLL_INTERVAL: u64 = 5;
+/// Interval increase required by RFC 8628 after the `slow_down` error.
+const SLOW_DOWN_INCREASE: u64 = 5;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum Flow {
+    ///Authorization code flow.
+    Code,
+    ///Device authorization flow.
+    Device,
+    ///Resource owner password credentials flow.
+    Password,
+    ///Refresh flow using an already obtained refresh token.
+    Refresh,
+}
+
+impl Display for Flow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Code => write!(f, "code"),
+            Self::Device => write!(f, "device"),
+            Self::Password => write!(f, "password"),
+            Self::Refresh => write!(f, "refresh"),
+        }
+    }
+}
+
+/// Result of a single device flow lookup.
+#[derive(Debug)]
+pub enum DevicePoll {
+    /// The user has not finished the authorization yet. Wait for the given duration before
+    /// polling again.
+    Pending(Duration),
+    /// The authorization succeeded and the account config was generated.
+    Done(AccountConfig),
+}
+
+/// State of a running device authorization flow.
+///
+/// The flow is started with [`crate::Agent::start_device_flow`]. The caller has to show the
+/// [`DeviceFlow::verification_uri`] and the [`DeviceFlow::user_code`] to the user and then poll
+/// the agent with [`crate::Agent::poll_device_flow`] until the generated config is returned.
+#[derive(Debug, Clone)]
+pub struct DeviceFlow {
+    config: AccountConfig,
+    device: DeviceAuthorization,
+    interval: Duration,
+    expires_at: Option<Instant>,
+}
+
+impl DeviceFlow {
+    /// Attempts to construct a new `DeviceFlow` from the agent answer to the
+    /// [`crate::requests::GenRequest`].
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - the response does not contain the device authorization or the account config.
+    pub fn from_response(response: GenResponse) -> AgentResult<Self> {
+        let (config, device) = match (response.config(), response.device()) {
+            (Some(config), Some(device)) => (config.clone(), device.clone()),
+            _ => return Err("The agent did not start the device flow!".into()),
+        };
+        let interval = Duration::from_secs(device.interval().unwrap_or(DEFAULT_POLL_INTERVAL));
+        let expires_at = device
+            .expires_in()
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+        Ok(Self {
+            config,
+            device,
+            interval,
+            expires_at,
+        })
+    }
+
+    /// Returns the device authorization returned by the issuer.
+    pub fn device(&self) -> &DeviceAuthorization {
+        &self.device
+    }
+
+    /// Returns the uri the user has to visit to authorize the device.
+    pub fn verification_uri(&self) -> &str {
+        self.device.verification_uri()
+    }
+
+    /// Returns the code the user has to enter at the [`DeviceFlow::verification_uri`].
+    pub fn user_code(&self) -> &str {
+        self.device.user_code()
+    }
+
+    /// Returns the current polling interval.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Checks whether the device code has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|exp| Instant::now() >= exp)
+    }
+
+    /// Creates the `device_lookup` request for this flow.
+    pub fn lookup_request(&self) -> DeviceLookupRequest {
+        DeviceLookupRequest::new(&self.config, &self.device)
+    }
+
+    /// Processes the agent answer to the [`DeviceFlow::lookup_request`].
+    ///
+    /// The `authorization_pending` and `slow_down` errors are turned into
+    /// [`DevicePoll::Pending`], every other error is returned.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - the device code has expired,
+    /// - the agent returned an error other than the pending ones,
+    /// - the agent returned a successful response without the account config.
+    pub fn handle_lookup(&mut self, result: AgentResult<GenResponse>) -> AgentResult<DevicePoll> {
+        match result {
+            Ok(response) => match response.config() {
+                Some(config) => Ok(DevicePoll::Done(config.clone())),
+                None => Err("The agent did not return the generated account config!".into()),
+            },
+            Err(Error::AgentError(e)) if is_pending(&e) => {
+                if e.error().contains("slow_down") {
+                    self.interval += Duration::from_secs(SLOW_DOWN_INCREASE);
+                }
+                if self.is_expired() {
+                    return Err("The device code has expired!".into());
+                }
+                Ok(DevicePoll::Pending(self.interval))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Next step of a flow started by the [`crate::requests::GenRequest`] or the
+/// [`crate::requests::ReauthenticateRequest`].
+#[derive(Debug)]
+pub enum Continuation {
+    /// The user has to authorize the device, see [`DeviceFlow`].
+    Device(DeviceFlow),
+    /// The user has to authorize in the browser, see [`AuthCodeFlow`].
+    AuthCode(AuthCodeFlow),
+    /// The flow finished without user interaction.
+    Done(AccountConfig),
+}
+
+impl Continuation {
+    /// Attempts to determine the next step from the agent answer.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - the response holds neither the account config nor the data needed to continue.
+    pub fn from_response(response: GenResponse) -> AgentResult<Self> {
+        if response.device().is_some() {
+            DeviceFlow::from_response(response).map(Self::Device)
+        } else if response.uri().is_some() {
+            AuthCodeFlow::from_response(response).map(Self::AuthCode)
+        } else if let Some(config) = response.config() {
+            Ok(Self::Done(config.clone()))
+        } else {
+            Err("The agent returned neither the account config nor the flow to continue!".into())
+        }
+    }
+}
+
+/// State of a running authorization code flow.
+///
+/// The flow is started with [`crate::Agent::start_auth_code_flow`]. The caller has to open the
+/// [`AuthCodeFlow::authorization_uri`] in the user's browser and then finish the flow with
+/// either [`crate::Agent::finish_auth_code_flow`] or [`crate::Agent::state_lookup`].
+#[derive(Debug, Clone)]
+pub struct AuthCodeFlow {
+    uri: Url,
+    state: String,
+}
+
+impl AuthCodeFlow {
+    /// Attempts to construct a new `AuthCodeFlow` from the agent answer to the
+    /// [`crate::requests::GenRequest`].
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - the response does not contain the authorization uri or the state.
+    pub fn from_response(response: GenResponse) -> AgentResult<Self> {
+        match (response.uri(), response.state()) {
+            (Some(uri), Some(state)) => Ok(Self {
+                uri: uri.clone(),
+                state: state.clone(),
+            }),
+            _ => Err("The agent did not start the authorization code flow!".into()),
+        }
+    }
+
+    /// Returns the uri that must be opened in the user's browser.
+    pub fn authorization_uri(&self) -> &Url {
+        &self.uri
+    }
+
+    /// Returns the state identifying this flow.
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    /// Attempts to create the `code_exchange` request from the uri the issuer redirected to.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - parsing the `redirected_uri` as an url fails,
+    /// - the `redirected_uri` does not match the `redirect_uri` of the authorization uri,
+    /// - the issuer returned an error instead of the code,
+    /// - the `redirected_uri` has no code or its state does not match this flow.
+    pub fn code_exchange_request(&self, redirected_uri: &str) -> AgentResult<CodeExchangeRequest> {
+        let uri = Url::parse(redirected_uri)?;
+        if let Some(redirect) = self.redirect_uri()
+            && redirect[..Position::AfterPath] != uri[..Position::AfterPath]
+        {
+            return Err("The redirected uri does not match the redirect uri of the flow!".into());
+        }
+        let param = |name: &str| {
+            uri.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        if let Some(error) = param("error") {
+            let description = param("error_description").unwrap_or_default();
+            return Err(Error::OtherError(format!(
+                "Authorization failed: {} {}",
+                error, description
+            )));
+        }
+        if param("code").is_none() {
+            return Err("The redirected uri contains no authorization code!".into());
+        }
+        if param("state").as_deref() != Some(self.state.as_str()) {
+            return Err("The redirected uri state does not match the flow!".into());
+        }
+        Ok(CodeExchangeRequest::new(&uri, &self.state))
+    }
+
+    /// Returns the `redirect_uri` the issuer was asked to redirect to, if the authorization uri
+    /// holds one.
+    fn redirect_uri(&self) -> Option<Url> {
+        self.uri
+            .query_pairs()
+            .find(|(key, _)| key == "redirect_uri")
+            .and_then(|(_, value)| Url::parse(&value).ok())
+    }
+
+    /// Creates the `state_lookup` request for this flow.
+    pub fn state_lookup_request(&self) -> StateLookupRequest {
+        StateLookupRequest::new(&self.state)
+    }
+
+    /// Attempts to extract the generated account config from the agent answer to the
+    /// `code_exchange` or `state_lookup` request.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - the response does not contain the account config.
+    pub fn handle_result(response: GenResponse) -> AgentResult<AccountConfig> {
+        match response.config() {
+            Some(config) => Ok(config.clone()),
+            None => Err("The agent did not return the generated account config!".into()),
+        }
+    }
+}
+
+fn is_pending(error: &AgentError) -> bool {
+    error.error().contains("authorization_pending") || error.error().contains("slow_down")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn gen_response(response: Value) -> GenResponse {
+        serde_json::from_value(response).unwrap()
+    }
+
+    fn agent_error(error: &str) -> AgentResult<GenResponse> {
+        let error = serde_json::from_value(json!({"status": "failure", "error": error}));
+        Err(Error::AgentError(error.unwrap()))
+    }
+
+    fn device_flow(expires_in: u64, interval: Option<u64>) -> DeviceFlow {
+        let response = gen_response(json!({
+            "status": "accepted",
+            "config": {"name": "profile"},
+            "oidc_device": {
+                "device_code": "device-code",
+                "user_code": "ABCD-EFGH",
+                "verification_uri": "https://issuer.example/device",
+                "expires_in": expires_in,
+                "interval": interval,
+            },
+        }));
+        DeviceFlow::from_response(response).unwrap()
+    }
+
+    #[test]
+    fn device_lookup_pending_then_done() {
+        let mut flow = device_flow(600, Some(2));
+        assert_eq!(flow.user_code(), "ABCD-EFGH");
+        assert_eq!(flow.interval(), Duration::from_secs(2));
+
+        let poll = flow.handle_lookup(agent_error("authorization_pending"));
+        assert!(matches!(poll, Ok(DevicePoll::Pending(i)) if i == Duration::from_secs(2)));
+
+        let response = gen_response(json!({"status": "success", "config": {"name": "profile"}}));
+        match flow.handle_lookup(Ok(response)) {
+            Ok(DevicePoll::Done(config)) => assert_eq!(config.shortname(), Some("profile")),
+            other => panic!("unexpected poll result: {:?}", other),
+        }
+
+        let response = gen_response(json!({"status": "success"}));
+        assert!(flow.handle_lookup(Ok(response)).is_err());
+        let poll = flow.handle_lookup(agent_error("access_denied"));
+        assert!(matches!(poll, Err(Error::AgentError(e)) if e.error() == "access_denied"));
+    }
+
+    #[test]
+    fn device_lookup_slows_down() {
+        let mut flow = device_flow(600, None);
+        assert_eq!(flow.interval(), Duration::from_secs(DEFAULT_POLL_INTERVAL));
+
+        let poll = flow.handle_lookup(agent_error("slow_down"));
+        assert!(matches!(poll, Ok(DevicePoll::Pending(i)) if i == Duration::from_secs(10)));
+        let poll = flow.handle_lookup(agent_error("slow_down"));
+        assert!(matches!(poll, Ok(DevicePoll::Pending(i)) if i == Duration::from_secs(15)));
+        let poll = flow.handle_lookup(agent_error("authorization_pending"));
+        assert!(matches!(poll, Ok(DevicePoll::Pending(i)) if i == Duration::from_secs(15)));
+        assert_eq!(flow.interval(), Duration::from_secs(15));
+    }
+
+    fn auth_code_flow() -> AuthCodeFlow {
+        let response = gen_response(json!({
+            "status": "accepted",
+            "uri": "https://issuer.example/authorize?response_type=code&client_id=client\
+                    &redirect_uri=http%3A%2F%2Flocalhost%3A4242%2Fcallback&state=0%3Aabc",
+            "state": "0:abc",
+        }));
+        AuthCodeFlow::from_response(response).unwrap()
+    }
+
+    #[test]
+    fn creates_code_exchange_request() {
+        let flow = auth_code_flow();
+        let request =
+            flow.code_exchange_request("http://localhost:4242/callback?code=c&state=0:abc");
+        let request = serde_json::to_value(request.unwrap()).unwrap();
+        assert_eq!(request["request"], "code_exchange");
+        assert_eq!(request["state"], "0:abc");
+    }
+
+    #[test]
+    fn rejects_invalid_redirects() {
+        let flow = auth_code_flow();
+        let rejected = [
+            // state mismatch
+            "http://localhost:4242/callback?code=c&state=0:other",
+            "http://localhost:4242/callback?code=c",
+            // issuer error
+            "http://localhost:4242/callback?error=access_denied&state=0:abc",
+            // missing code
+            "http://localhost:4242/callback?state=0:abc",
+            // not the redirect uri
+            "http://localhost:4243/callback?code=c&state=0:abc",
+            "http://localhost:4242/other?code=c&state=0:abc",
+            "https://localhost:4242/callback?code=c&state=0:abc",
+            "http://attacker.example:4242/callback?code=c&state=0:abc",
+            "not an uri",
+        ];
+        for uri in rejected {
+            assert!(flow.code_exchange_request(uri).is_err(), "{uri}");
+        }
+
+        let error = flow
+            .code_exchange_request(
+                "http://localhost:4242/callback?error=access_denied\
+                 &error_description=The+user+declined&state=0:abc",
+            )
+            .unwrap_err();
+        assert!(error.to_string().contains("access_denied"));
+        assert!(error.to_string().contains("The user declined"));
+    }
+
+    #[test]
+    fn device_lookup_expires() {
+        let mut flow = device_flow(0, Some(1));
+        assert!(flow.is_expired());
+        assert!(
+            flow.handle_lookup(agent_error("authorization_pending"))
+                .is_err()
+        );
+
+        // The generated config is taken even if it arrives after the expiry.
+        let response = gen_response(json!({"status": "success", "config": {"name": "profile"}}));
+        assert!(matches!(
+            flow.handle_lookup(Ok(response)),
+            Ok(DevicePoll::Done(_))
+        ));
+
+        assert!(!device_flow(600, Some(1)).is_expired());
+    }
+}
@@ -9,7 +9,7 @@
 //! # Obtaining access_token
 //! ## Basic usage
 //! To obtain access_token by account shortname run the following code in `main.rs`:
-//! ```
+//! ```no_run
 //! use oidc_agent_rs::{Agent, Error};
 //!
 //! fn main() -> Result<(), Error> {
@@ -26,7 +26,7 @@
 //! ## Asynchronous Usage
 //! For asynchronous programming, you need to enable the `async` feature and use the [`crate::async_impl::Agent`].
 //! Here’s a basic example of obtaining an access_token asynchronously:
-//! ```rust
+//! ```ignore
 //! use oidc_agent_rs::async_impl::Agent;
 //! use oidc_agent_rs::Error;
 //!
@@ -43,17 +43,26 @@
 //! Cargo.toml
 //!```toml
 //! [dependencies]
-//! oidc_agent_rs = { version = "0.2.0", features=["async"]}
+//! oidc_agent_rs = { version = "0.3.0", features=["async"]}
 //! tokio = { version = "1.39.2", features = ["net", "io-util", "macros", "rt-multi-thread"] }
 //! ```
 //!
+//! The `async` feature runs the agent on tokio. For `smol` or `async-std` enable the `smol` feature
+//! and build the agent with the [`AgentBuilder::build_smol`]:
+//! ```ignore
+//! let agent = Agent::builder().build_smol().await?;
+//! let at = agent.get_access_token("profile_shortname").await?;
+//! ```
+//! Other runtimes can be plugged in with the [`crate::async_impl::AsyncTransport`] and the
+//! [`crate::async_impl::runtime::AsyncStream`].
+//!
 //! ## Advanced requests
 //! To obtain access_token with more advanced options you have to use request builder.
 //! [ `AccessTokenRequest` ] has a method to easy build a new request. Then you have to send the request
 //! directly to the agent and parse the response.
 //!
 //! Example:
-//! ```
+//! ```no_run
 //! use oidc_agent_rs::{requests::AccessTokenRequest, Agent, Error};
 //!
 //! fn main() -> Result<(), Error> {
@@ -80,7 +89,7 @@
 //! Obtaining mytoken using only account shortname is very similar to obtaining access_token.
 //!
 //! Example:
-//! ```
+//! ```no_run
 //! use oidc_agent_rs::{Agent, Error};
 //!
 //! fn main() -> Result<(), Error> {
@@ -104,7 +113,7 @@
 //!
 //! Example:
 //!
-//!```
+//!```no_run
 //! use oidc_agent_rs::mytoken::{Capability, Profile, Restriction, Rotation, TokenInfoPerms};
 //! use oidc_agent_rs::requests::MyTokenRequest;
 //! use oidc_agent_rs::{Agent, Error};
@@ -145,72 +154,219 @@
 #![cfg(unix)]
 
 /// An asynchronous Agent API
-#[cfg(feature = "async")]
+#[cfg(any(feature = "async", feature = "smol"))]
 pub mod async_impl;
+/// Agent builder
+pub mod builder;
+/// Access token cache
+pub mod cache;
+/// Sans-IO protocol codec
+pub mod codec;
+/// Persistent agent connection
+pub mod connection;
+/// Agent socket discovery
+pub mod discovery;
 /// Errors
 pub mod errors;
+/// Account generation utils
+pub mod generate;
 /// Mytoken utils
 pub mod mytoken;
+/// Socket peer verification
+pub mod peer;
+/// Agent process utils
+pub mod process;
+/// Token providers
+pub mod provider;
 /// Requests
 pub mod requests;
 /// Responses
 pub mod responses;
+/// Agent socket address
+pub mod socket;
+/// Mock agent for tests
+#[cfg(feature = "testing")]
+pub mod testing;
+/// Agent transports
+pub mod transport;
 
-use errors::AgentError;
+pub use builder::AgentBuilder;
+use builder::AgentOptions;
+pub use cache::CachedAgent;
+pub use connection::Connection;
 pub use errors::Error;
-use requests::{AccessTokenRequest, AccountsRequest, MyTokenRequest};
-use responses::{AccessTokenResponse, MyTokenResponse};
-use responses::{OIDCAgentResponse, Status};
+pub use provider::TokenProvider;
+use generate::{AuthCodeFlow, Continuation, DeviceFlow, DevicePoll};
+use process::{AgentProcess, AgentStartup};
+use requests::{
+    AccessTokenRequest, AccountInfoRequest, AccountsRequest, AddRequest, CheckRequest,
+    DeleteRequest, GenRequest, GenericRequest, IdTokenRequest, LockRequest, MyTokenRequest,
+    ReauthenticateRequest, RegisterRequest, RemoveAllRequest, RemoveRequest, ScopesRequest,
+    UnlockRequest,
+};
+use responses::{
+    AccessTokenResponse, AccountInfo, IdTokenResponse, MyTokenResponse, RegisteredClient,
+    StatusResponse,
+};
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
-use std::env;
+use socket::SocketAddress;
+use std::collections::HashSet;
+use std::ffi::OsStr;
 use std::fmt::Debug;
-use std::io::prelude::*;
-use std::io::Write;
-use std::os::unix::net::UnixStream;
 use std::path::Path;
-use std::path::PathBuf;
+pub use transport::{Transport, UnixTransport};
 
 pub type AgentResult<T> = Result<T, Error>;
 
 pub trait Request: Serialize {
     type SuccessResponse: Response;
+
+    /// Returns the `application_hint` field of the request, if the request has one. It is used to
+    /// apply the default application hint set by the [`AgentBuilder::application_hint`].
+    fn application_hint_mut(&mut self) -> Option<&mut Option<String>> {
+        None
+    }
+}
+pub trait Response: DeserializeOwned {
+    /// Whether the response may come with the [`responses::Status::ACCEPTED`] status, i.e. the
+    /// request starts or continues a flow. For the other responses the `accepted` status is
+    /// returned as the [`Error::UnexpectedStatusError`].
+    const ACCEPTS_ACCEPTED: bool = false;
 }
-pub trait Response: DeserializeOwned {}
 
 #[derive(Debug, Clone)]
-pub struct Agent {
-    socket_path: PathBuf,
+pub struct Agent<T = UnixTransport> {
+    transport: T,
+    options: AgentOptions,
 }
 
 impl Agent {
     /// Attempts to construct a new `Agent`.
     ///
-    /// It attempts to retrieve the socket path from the `OIDC_SOCK` environment variable.
+    /// It attempts to retrieve the socket path from the `OIDC_SOCK` environment variable. Only if
+    /// the variable is not set, the default `oidc-agent-service` socket locations are tried, see
+    /// [`discovery::discover`].
+    /// Use the [`Agent::builder`] to set the socket path explicitly or to set the timeouts.
     /// # Errors
     /// The method returns an coresponding [`Error`] if:
-    /// - the environment variable `OIDC_SOCK` is not set or cannot be retrieved.
-    /// - connection with provided socket is not possible.
+    /// - no usable socket was found, the [`Error::DiscoveryError`] lists every tried location.
+    /// - connection with provided socket is not possible, e.g. the `OIDC_SOCK` socket is dead or
+    ///   fails the peer verification.
     pub fn new() -> AgentResult<Self> {
-        let socket_var = env::var("OIDC_SOCK")?;
-        let socket_path = Path::new(&socket_var);
+        AgentBuilder::new().build()
+    }
 
-        //Check the connection
-        UnixStream::connect(socket_path)?;
-        Ok(Self {
-            socket_path: socket_path.into(),
-        })
+    /// Creates a new [`AgentBuilder`] to build an agent.
+    pub fn builder() -> AgentBuilder {
+        AgentBuilder::new()
+    }
+
+    /// Attempts to construct a new `Agent` from the output printed by `oidc-agent` or
+    /// `oidc-agent-service` at startup, see [`AgentStartup`]. Returns the agent and the PID of the
+    /// agent process.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - the output cannot be parsed,
+    /// - connection with the socket is not possible.
+    pub fn from_startup_output(output: &str) -> AgentResult<(Self, u32)> {
+        let startup = AgentStartup::parse(output)?;
+        let agent = startup.builder().build()?;
+        Ok((agent, startup.pid()))
     }
 
-    /// Retrives the agent socket path.
+    /// Attempts to start a private `oidc-agent` listening on a socket in a new temporary
+    /// directory. The agent process is killed when the returned [`AgentProcess`] is dropped.
+    ///
+    /// The `oidc-agent` binary is looked up in the `PATH`.
     /// # Examples
+    /// ```ignore
+    /// let (agent, _process) = Agent::spawn()?;
+    /// agent.unlock("password")?;
     /// ```
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - the agent cannot be started, see [`Agent::spawn_with_args`],
+    /// - connection with the spawned agent is not possible.
+    pub fn spawn() -> AgentResult<(Self, AgentProcess)> {
+        Self::spawn_with_args(std::iter::empty::<&OsStr>())
+    }
+
+    /// The same as [`Agent::spawn`], but passes additional `args` to the `oidc-agent`, e.g.
+    /// `--no-autoload`.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - creating the socket directory fails,
+    /// - the `oidc-agent` cannot be started or exits with an error,
+    /// - the agent output cannot be parsed or the socket does not appear in time,
+    /// - connection with the spawned agent is not possible.
+    pub fn spawn_with_args<I, S>(args: I) -> AgentResult<(Self, AgentProcess)>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let process = AgentProcess::spawn(args)?;
+        let agent = process.startup().builder().build()?;
+        Ok((agent, process))
+    }
+
+    pub(crate) fn from_parts(socket: SocketAddress, options: AgentOptions) -> Self {
+        Self {
+            transport: UnixTransport::with_options(socket, options.clone()),
+            options,
+        }
+    }
+
+    /// Retrives the agent socket path. Returns `None` for abstract sockets, use the
+    /// [`Agent::get_socket_address`] for them.
+    /// # Examples
+    /// ```ignore
     /// let agent = Agent::new()?;
     /// assert_eq!(agent.get_socket_path(), Some("/tmp/oidc-agent-service-1000/oidc-agent.sock"))
     /// ```
     pub fn get_socket_path(&self) -> Option<&str> {
-        self.socket_path.to_str()
+        self.transport.socket().as_path().and_then(Path::to_str)
+    }
+
+    /// Retrives the agent socket address.
+    pub fn get_socket_address(&self) -> &SocketAddress {
+        self.transport.socket()
+    }
+}
+
+impl<T: Transport> Agent<T> {
+    /// Creates a new `Agent` sending the requests over the custom `transport`. Use the
+    /// [`AgentBuilder::build_with_transport`] to set the default `application_hint` as well.
+    pub fn with_transport(transport: T) -> Self {
+        Self::from_transport(transport, AgentOptions::default())
+    }
+
+    pub(crate) fn from_transport(transport: T, options: AgentOptions) -> Self {
+        Self { transport, options }
+    }
+
+    pub(crate) fn connect(&self) -> AgentResult<T::Stream> {
+        self.transport.connect()
+    }
+
+    /// Attempts to check whether the agent behind the socket is alive and responding. Unlike
+    /// [`Agent::new`], which only connects to the socket, this method exchanges a message with the
+    /// agent.
+    ///
+    /// The [`requests::CheckRequest`] is used as a request here.
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    /// # Examples
+    /// ```ignore
+    /// let status = agent.status()?;
+    /// assert!(status.version_at_least(5, 0, 0));
+    /// assert_eq!(status.locked(), Some(false));
+    /// ```
+    pub fn status(&self) -> AgentResult<StatusResponse> {
+        let request = CheckRequest::new();
+        let response = self.send_request(request)?;
+        Ok(response)
     }
 
     /// Attempts to obtain access_token using only `account_shortname`. No more fields are added to the
@@ -221,7 +377,7 @@ impl Agent {
     /// The same as [`Agent::send_request`].
     ///
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let access_token = agent.get_access_token("shortname")?;
     /// assert_eq!(access_token.secret(), "eyJh...");
     /// ```
@@ -234,7 +390,7 @@ impl Agent {
     /// The same as [`Agent::get_access_token`], but if the response is successful, the
     /// [`responses::AccessTokenResponse`] is returned, allowing you to access additional fields.
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let at = agent.get_access_token_full("shortname")?;
     /// assert_eq!(at.expires_at(), expiration_date);
     /// assert_eq!(at.issuer(), issuer);
@@ -249,6 +405,38 @@ impl Agent {
         Ok(response)
     }
 
+    /// Attempts to obtain id_token using only `account_shortname`. No more fields are added to the
+    /// request.
+    ///
+    /// The [`requests::IdTokenRequest::basic`] is used as a request here.
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let id_token = agent.get_id_token("shortname")?;
+    /// assert_eq!(id_token.secret(), "eyJh...");
+    /// ```
+    pub fn get_id_token(&self, account_shortname: &str) -> AgentResult<Token> {
+        let request = IdTokenRequest::basic(account_shortname);
+        let response = self.send_request(request)?;
+        Ok(response.id_token().clone())
+    }
+
+    /// The same as [`Agent::get_id_token`], but if the response is successful, the
+    /// [`responses::IdTokenResponse`] is returned, allowing you to access additional fields.
+    /// # Examples
+    /// ```ignore
+    /// let it = agent.get_id_token_full("shortname")?;
+    /// assert_eq!(it.issuer(), issuer);
+    /// assert_eq!(it.id_token().secret(), id_token);
+    /// ```
+    pub fn get_id_token_full(&self, account_shortname: &str) -> AgentResult<IdTokenResponse> {
+        let request = IdTokenRequest::basic(account_shortname);
+        let response = self.send_request(request)?;
+        Ok(response)
+    }
+
     /// Attempts to obtain [mytoken](https://mytoken-docs.data.kit.edu/) using only `account_shortname`. No more fields are added to the
     /// request.
     ///
@@ -257,7 +445,7 @@ impl Agent {
     /// The same as [`Agent::send_request`].
     ///
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let mytoken = agent.get_mytoken("shortname")?;
     /// assert_eq!(mytoken.secret(), "eyJh...");
     /// ```
@@ -270,7 +458,7 @@ impl Agent {
     /// The same as [`Agent::get_mytoken`], but if the response is successful, the
     /// [`responses::MyTokenResponse`] is returned, allowing you to access additional fields.
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let mt = agent.get_mytoken_full("shortname")?;
     /// assert_eq!(mt.mytoken_issuer(), mt_issuer);
     /// assert_eq!(mt.oidc_issuer(), issuer);
@@ -288,7 +476,7 @@ impl Agent {
     /// # Errors
     /// The same as [`Agent::send_request`].
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let accounts = agent.get_loaded_accounts()?;
     /// assert_eq!(accounts, vec!["my_account"]);
     /// ```
@@ -298,40 +486,329 @@ impl Agent {
         Ok(response.info().clone())
     }
 
+    /// Attempts to get information about every configured account. Unlike
+    /// [`Agent::get_loaded_accounts`] it also returns accounts that are not loaded, together with
+    /// their issuer and client type.
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    /// # Examples
+    /// ```ignore
+    /// let accounts = agent.get_accounts_info()?;
+    /// let egi = accounts.iter().find(|a| a.issuer().as_str() == "https://aai.egi.eu/auth/realms/egi/");
+    /// ```
+    pub fn get_accounts_info(&self) -> AgentResult<Vec<AccountInfo>> {
+        let request = AccountInfoRequest::new();
+        let response = self.send_request(request)?;
+        Ok(response.accounts())
+    }
+
+    /// Attempts to get the set of scopes supported by the `issuer`. To query the issuer of a
+    /// loaded account use the [`requests::ScopesRequest::builder`] instead.
+    /// # Errors
+    /// The same as [`Agent::send_request`]. Also an [`Error`] is returned if parsing the
+    /// `issuer` as an url fails.
+    /// # Examples
+    /// ```ignore
+    /// let scopes = agent.get_supported_scopes("https://issuer.url")?;
+    /// assert!(scopes.contains("openid"));
+    /// ```
+    pub fn get_supported_scopes(&self, issuer: &str) -> AgentResult<HashSet<String>> {
+        let request = ScopesRequest::builder().issuer(issuer)?.build()?;
+        let response = self.send_request(request)?;
+        Ok(response.scopes())
+    }
+
+    /// Attempts to lock the agent with the `password`. While locked, the agent refuses to hand out
+    /// any tokens until it is unlocked with the same password.
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    /// # Examples
+    /// ```ignore
+    /// agent.lock("secret")?;
+    /// assert!(agent.get_access_token("shortname").is_err());
+    /// ```
+    pub fn lock(&self, password: &str) -> AgentResult<()> {
+        let request = LockRequest::new(password);
+        self.send_request(request)?;
+        Ok(())
+    }
+
+    /// Attempts to unlock the agent previously locked with [`Agent::lock`].
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    /// # Examples
+    /// ```ignore
+    /// agent.unlock("secret")?;
+    /// let access_token = agent.get_access_token("shortname")?;
+    /// ```
+    pub fn unlock(&self, password: &str) -> AgentResult<()> {
+        let request = UnlockRequest::new(password);
+        self.send_request(request)?;
+        Ok(())
+    }
+
+    /// Attempts to load the account described by the decrypted `config` into the agent. No
+    /// lifetime is set and no confirmation is required.
+    ///
+    /// The request is built with the [`requests::AddRequest::builder`] without any flags. Use the
+    /// builder directly to set the lifetime or the confirmation flags.
+    /// # Errors
+    /// The same as [`Agent::send_request`]. Also an [`Error`] is returned if the `config` has no
+    /// account shortname.
+    /// # Examples
+    /// ```ignore
+    /// let config = AccountConfig::from_json(&decrypted_config)?;
+    /// agent.add_account(&config)?;
+    /// assert!(agent.get_loaded_accounts()?.contains(&"shortname".to_string()));
+    /// ```
+    pub fn add_account(&self, config: &AccountConfig) -> AgentResult<()> {
+        let request = AddRequest::builder(config).build()?;
+        self.send_request(request)?;
+        Ok(())
+    }
+
+    /// Attempts to unload the account identified by `account_shortname` from the agent.
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    /// # Examples
+    /// ```ignore
+    /// agent.remove_account("shortname")?;
+    /// ```
+    pub fn remove_account(&self, account_shortname: &str) -> AgentResult<()> {
+        let request = RemoveRequest::new(account_shortname);
+        self.send_request(request)?;
+        Ok(())
+    }
+
+    /// Attempts to unload every account from the agent.
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    /// # Examples
+    /// ```ignore
+    /// agent.remove_all_accounts()?;
+    /// assert!(agent.get_loaded_accounts()?.is_empty());
+    /// ```
+    pub fn remove_all_accounts(&self) -> AgentResult<()> {
+        let request = RemoveAllRequest::new();
+        self.send_request(request)?;
+        Ok(())
+    }
+
+    /// Attempts to revoke the refresh token of the account at its issuer. The account does not
+    /// have to be loaded.
+    ///
+    /// The tokens cannot be revoked by the account shortname alone. The agent's `delete` call
+    /// takes the decrypted account configuration, because the refresh token and the client
+    /// credentials needed for the revocation are stored in the encrypted account file, which the
+    /// agent does not read. Decrypt the account file first, e.g. with
+    /// `oidc-gen --print <account_shortname>`, and pass the result as the `config`, the same one
+    /// the [`Agent::add_account`] takes.
+    /// # Errors
+    /// The same as [`Agent::send_request`], but every error reported by the agent, e.g. the
+    /// issuer refused the revocation, is returned as the [`Error::RevocationFailed`].
+    /// # Examples
+    /// ```ignore
+    /// let config = AccountConfig::from_json(&decrypted_config)?;
+    /// agent.revoke_account_tokens(&config)?;
+    /// ```
+    pub fn revoke_account_tokens(&self, config: &AccountConfig) -> AgentResult<()> {
+        let request = DeleteRequest::new(config);
+        self.send_request(request)
+            .map_err(Error::into_revocation_error)?;
+        Ok(())
+    }
+
+    /// Attempts to start the generation of a new account using the device authorization flow.
+    /// The [`requests::GenRequest`] should allow the [`generate::Flow::Device`] flow.
+    ///
+    /// The returned [`generate::DeviceFlow`] holds the verification uri and the user code that
+    /// must be shown to the user. Then call [`Agent::poll_device_flow`] to wait for the
+    /// generated account config.
+    /// # Errors
+    /// The same as [`Agent::send_request`]. Also an [`Error`] is returned if the agent did not
+    /// start the device flow.
+    /// # Examples
+    /// ```ignore
+    /// let request = GenRequest::builder("shortname")
+    ///     .issuer("https://issuer.url")?
+    ///     .add_flow(Flow::Device)
+    ///     .build()?;
+    /// let mut flow = agent.start_device_flow(request)?;
+    /// println!("Visit {} and enter {}", flow.verification_uri(), flow.user_code());
+    /// let config = agent.poll_device_flow(&mut flow)?;
+    /// ```
+    pub fn start_device_flow(&self, request: GenRequest) -> AgentResult<DeviceFlow> {
+        let response = self.send_request(request)?;
+        DeviceFlow::from_response(response)
+    }
+
+    /// Sends a single `device_lookup` request for the running [`generate::DeviceFlow`].
+    /// # Errors
+    /// The same as [`generate::DeviceFlow::handle_lookup`].
+    pub fn device_lookup(&self, flow: &mut DeviceFlow) -> AgentResult<DevicePoll> {
+        let result = self.send_request(flow.lookup_request());
+        flow.handle_lookup(result)
+    }
+
+    /// Polls the agent until the user authorizes the device and returns the generated
+    /// [`AccountConfig`]. The current thread sleeps between the lookups for the interval
+    /// requested by the issuer.
+    /// # Errors
+    /// The same as [`Agent::device_lookup`].
+    pub fn poll_device_flow(&self, flow: &mut DeviceFlow) -> AgentResult<AccountConfig> {
+        loop {
+            match self.device_lookup(flow)? {
+                DevicePoll::Pending(interval) => std::thread::sleep(interval),
+                DevicePoll::Done(config) => return Ok(config),
+            }
+        }
+    }
+
+    /// Attempts to start the generation of a new account using the authorization code flow.
+    /// The [`requests::GenRequest`] should allow the [`generate::Flow::Code`] flow.
+    ///
+    /// The returned [`generate::AuthCodeFlow`] holds the authorization uri that must be opened in
+    /// the user's browser. Once the issuer redirects the browser, pass the redirected uri to
+    /// [`Agent::finish_auth_code_flow`]. If the redirect is handled by the agent itself, use
+    /// [`Agent::state_lookup`] instead.
+    /// # Errors
+    /// The same as [`Agent::send_request`]. Also an [`Error`] is returned if the agent did not
+    /// start the authorization code flow.
+    /// # Examples
+    /// ```ignore
+    /// let request = GenRequest::builder("shortname")
+    ///     .issuer("https://issuer.url")?
+    ///     .add_redirect_uri("http://localhost:8080")?
+    ///     .add_flow(Flow::Code)
+    ///     .build()?;
+    /// let flow = agent.start_auth_code_flow(request)?;
+    /// open_browser(flow.authorization_uri());
+    /// let config = agent.finish_auth_code_flow(&flow, &redirected_uri)?;
+    /// ```
+    pub fn start_auth_code_flow(&self, request: GenRequest) -> AgentResult<AuthCodeFlow> {
+        let response = self.send_request(request)?;
+        AuthCodeFlow::from_response(response)
+    }
+
+    /// Attempts to exchange the authorization code from the `redirected_uri` and returns the
+    /// generated [`AccountConfig`].
+    /// # Errors
+    /// The same as [`Agent::send_request`]. Also an [`Error`] is returned if the `redirected_uri`
+    /// does not belong to the `flow`.
+    pub fn finish_auth_code_flow(
+        &self,
+        flow: &AuthCodeFlow,
+        redirected_uri: &str,
+    ) -> AgentResult<AccountConfig> {
+        let request = flow.code_exchange_request(redirected_uri)?;
+        let response = self.send_request(request)?;
+        AuthCodeFlow::handle_result(response)
+    }
+
+    /// Checks whether the agent has already finished the `flow` on its own, e.g. when the
+    /// redirect was received by the agent. Returns `None` if the flow is not finished yet.
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    pub fn state_lookup(&self, flow: &AuthCodeFlow) -> AgentResult<Option<AccountConfig>> {
+        match self.send_request(flow.state_lookup_request()) {
+            Ok(response) => AuthCodeFlow::handle_result(response).map(Some),
+            Err(Error::NotFoundError(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Attempts to register a new client at the issuer using the dynamic client registration.
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    /// # Examples
+    /// ```ignore
+    /// let request = RegisterRequest::builder()
+    ///     .issuer("https://issuer.url")?
+    ///     .add_redirect_uri("http://localhost:8080")?
+    ///     .add_scope("openid profile offline_access")
+    ///     .add_flow(Flow::Code)
+    ///     .build()?;
+    /// let client = agent.register_client(request)?;
+    /// println!("{}", client.client_id());
+    /// ```
+    pub fn register_client(&self, request: RegisterRequest) -> AgentResult<RegisteredClient> {
+        let response = self.send_request(request)?;
+        Ok(response.client().clone())
+    }
+
+    /// Attempts to obtain a new refresh token for the account identified by
+    /// `account_shortname`, e.g. after the [`Error::ReauthenticationRequired`] was returned.
+    ///
+    /// The returned [`generate::Continuation`] is finished the same way as the account
+    /// generation, using either [`Agent::poll_device_flow`] or [`Agent::finish_auth_code_flow`].
+    /// # Errors
+    /// The same as [`Agent::send_request`].
+    /// # Examples
+    /// ```ignore
+    /// match agent.get_access_token("shortname") {
+    ///     Err(Error::ReauthenticationRequired(_)) => match agent.reauthenticate("shortname")? {
+    ///         Continuation::Device(mut flow) => {
+    ///             println!("Visit {} and enter {}", flow.verification_uri(), flow.user_code());
+    ///             agent.poll_device_flow(&mut flow)?;
+    ///         }
+    ///         Continuation::AuthCode(flow) => open_browser(flow.authorization_uri()),
+    ///         Continuation::Done(_) => {}
+    ///     },
+    ///     result => println!("{}", result?.secret()),
+    /// }
+    /// ```
+    pub fn reauthenticate(&self, account_shortname: &str) -> AgentResult<Continuation> {
+        let request = ReauthenticateRequest::new(account_shortname);
+        let response = self.send_request(request)?;
+        Continuation::from_response(response)
+    }
+
+    /// Sends the raw JSON `request` to the agent and returns the raw JSON response. Useful for
+    /// IPC requests that are not wrapped by this crate yet.
+    ///
+    /// The [`requests::GenericRequest`] is used as a request here.
+    /// # Errors
+    /// The same as [`Agent::send_request`]. Also an [`Error`] is returned if the `request` is
+    /// not a JSON object with the `request` field.
+    /// # Examples
+    /// ```ignore
+    /// let response = agent.send_raw(serde_json::json!({"request": "loaded_accounts"}))?;
+    /// assert_eq!(response["status"], "success");
+    /// ```
+    pub fn send_raw(&self, request: serde_json::Value) -> AgentResult<serde_json::Value> {
+        let request = GenericRequest::<serde_json::Value>::new(request)?;
+        self.send_request(request)
+    }
+
     /// Consumes the [`Request`], sends it to the oidc-agent stream socket and attempts to retrives the [`Response`].
     /// # Errors
     /// The method returns an coresponding [`Error`] if:
     /// - connection with socket is not possible anymore,
+    /// - the socket or the agent process belongs to another user, see [`peer`],
     /// - cannot write or read the socket,
     /// - the socket response cannot be deserialized,
-    /// - the oidc-agnet returned an error.
+    /// - the oidc-agnet returned an error,
+    /// - the oidc-agent returned a status other than `success` or `accepted`, see [`responses::Status`].
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let req = AccessTokenRequest::basic("mytoken");
     /// let resp = agent.send_request(req)?;
     /// assert_eq!(resp.access_token().secret(), access_token);
     /// ```
-    pub fn send_request<T>(&self, request: T) -> AgentResult<T::SuccessResponse>
+    pub fn send_request<R>(&self, request: R) -> AgentResult<R::SuccessResponse>
     where
-        T: Request,
+        R: Request,
     {
-        let mut socket = UnixStream::connect(Path::new(&self.socket_path))?;
-        let req = serde_json::to_vec(&request)?;
-        socket.write_all(&req)?;
-
-        let mut buffer = Vec::new();
-        socket.read_to_end(&mut buffer)?;
-        let resp: OIDCAgentResponse = serde_json::from_slice(&buffer)?;
-        match resp.status() {
-            Status::SUCCESS => {
-                let r: T::SuccessResponse = serde_json::from_slice(&buffer)?;
-                Ok(r)
-            }
-            Status::FAILURE => {
-                let r: AgentError = serde_json::from_slice(&buffer)?;
-                Err(r.into())
-            }
-        }
+        self.connection()?.send_request(request)
+    }
+
+    /// Attempts to open a persistent [`Connection`] to send many requests over one stream.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - connection with socket is not possible anymore,
+    /// - the socket or the agent process belongs to another user, see [`peer`].
+    pub fn connection(&self) -> AgentResult<Connection<T::Stream>> {
+        Ok(Connection::new(self.connect()?, self.options.clone()))
     }
 }
 
@@ -351,3 +828,74 @@ impl Debug for Token {
         write!(f, "Token([redacted])")
     }
 }
+
+/// Decrypted account configuration pseudostruct. The configuration holds the refresh token and
+/// the client credentials, so just like [`Token`] it never prints its content.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountConfig(serde_json::Value);
+
+impl AccountConfig {
+    /// Attempts to parse the decrypted account configuration, e.g. the output of
+    /// `oidc-gen --print <account_shortname>`.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - `config` is not a valid JSON,
+    /// - `config` is not a JSON object.
+    pub fn from_json(config: &str) -> AgentResult<Self> {
+        let value: serde_json::Value = serde_json::from_str(config)?;
+        if !value.is_object() {
+            return Err("Failed to parse account config! JSON object required!".into());
+        }
+        Ok(Self(value))
+    }
+
+    /// Returns the account shortname stored in the config.
+    pub fn shortname(&self) -> Option<&str> {
+        self.0.get("name").and_then(|n| n.as_str())
+    }
+
+    /// Returns the issuer url stored in the config.
+    pub fn issuer(&self) -> Option<&str> {
+        self.0.get("issuer_url").and_then(|i| i.as_str())
+    }
+
+    /// Returns the actual config.
+    pub fn secret(&self) -> &serde_json::Value {
+        &self.0
+    }
+
+    pub(crate) fn empty() -> Self {
+        Self(serde_json::Value::Object(serde_json::Map::new()))
+    }
+
+    pub(crate) fn set<T: Into<serde_json::Value>>(&mut self, key: &str, value: T) {
+        if let Some(config) = self.0.as_object_mut() {
+            config.insert(key.to_string(), value.into());
+        }
+    }
+
+    pub(crate) fn add_scope(&mut self, scope: &str) {
+        let scope = match self.0.get("scope").and_then(|s| s.as_str()) {
+            Some(curr_scope) => format!("{} {}", curr_scope, scope),
+            None => scope.trim().to_string(),
+        };
+        self.set("scope", scope);
+    }
+
+    pub(crate) fn add_redirect_uri(&mut self, redirect_uri: &url::Url) {
+        let mut uris = self
+            .0
+            .get("redirect_uris")
+            .and_then(|u| u.as_array())
+            .cloned()
+            .unwrap_or_default();
+        uris.push(redirect_uri.as_str().into());
+        self.set("redirect_uris", uris);
+    }
+}
+
+impl Debug for AccountConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AccountConfig([redacted])")
+    }
+}
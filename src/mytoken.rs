@@ -143,7 +143,7 @@ pub enum MyTokenType {
     TRANSER_CODE,
 }
 
-#[derive(Serialize, Deserialize, Debug, Hash, Eq, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Hash, Eq, PartialEq, Clone)]
 #[allow(non_snake_case)]
 pub struct Restriction {
     #[serde(default, with = "chrono::serde::ts_seconds_option")]
@@ -176,22 +176,6 @@ pub struct Restriction {
     usages_other: Option<u64>,
 }
 
-impl Default for Restriction {
-    fn default() -> Self {
-        Self {
-            nbf: None,
-            exp: None,
-            scope: None,
-            audience: None,
-            ip: None,
-            geoip_allow: None,
-            geoip_disallow: None,
-            usages_AT: None,
-            usages_other: None,
-        }
-    }
-}
-
 #[allow(non_snake_case)]
 impl Restriction {
     pub fn new() -> Self {
@@ -205,7 +189,7 @@ impl Restriction {
     }
     pub fn add_scope<T: ToString>(&mut self, scope: T) {
         if let Some(ref mut curr_scope) = self.scope {
-            curr_scope.push_str(" ");
+            curr_scope.push(' ');
             curr_scope.push_str(&scope.to_string());
         } else {
             self.scope = Some(scope.to_string().trim().to_string());
@@ -398,7 +382,7 @@ impl RestrictionBuilder {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Profile {
     #[serde(skip_serializing_if = "Option::is_none")]
     capabilities: Option<HashSet<Capability>>,
@@ -410,16 +394,6 @@ pub struct Profile {
     rotation: Option<Rotation>,
 }
 
-impl Default for Profile {
-    fn default() -> Self {
-        Self {
-            capabilities: None,
-            restrictions: None,
-            rotation: None,
-        }
-    }
-}
-
 impl Profile {
     pub fn new() -> Self {
         Profile::default()
@@ -0,0 +1,223 @@
+use std::fmt::Display;
+use std::io;
+use std::fs::Metadata;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::discovery::current_uid;
+use crate::{AgentResult, Error};
+
+/// Group and other permission bits. The socket (or its directory) must not grant any of them.
+const FOREIGN_ACCESS: u32 = 0o077;
+
+/// Reason why the agent socket failed the peer verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerMismatch {
+    /// The process listening on the socket runs as a different user.
+    PeerUid { expected: u32, actual: u32 },
+    /// The socket file is owned by a different user.
+    SocketOwner { expected: u32, actual: u32 },
+    /// Other users can access the socket, neither the socket nor its directory restrict the
+    /// access to the owner.
+    SocketMode(u32),
+}
+
+impl Display for PeerMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PeerUid { expected, actual } => write!(
+                f,
+                "agent process runs as uid {}, expected uid {}",
+                actual, expected
+            ),
+            Self::SocketOwner { expected, actual } => write!(
+                f,
+                "socket is owned by uid {}, expected uid {}",
+                actual, expected
+            ),
+            Self::SocketMode(mode) => write!(f, "socket is accessible by others: {:o}", mode),
+        }
+    }
+}
+
+/// Checks that the socket file at `path` belongs to the current user and that other users cannot
+/// access it. The access is restricted if either the socket itself or its parent directory (owned
+/// by the current user) grants no permissions to the group and others, as `oidc-agent` does.
+pub(crate) fn verify_socket_file(path: &Path) -> AgentResult<()> {
+    let socket = FileOwnership::from(&path.metadata()?);
+    let dir = path
+        .parent()
+        .and_then(|dir| dir.metadata().ok())
+        .map(|dir| FileOwnership::from(&dir));
+    check_socket_file(current_uid(), socket, dir).map_err(Error::PeerVerificationError)
+}
+
+/// Owner and mode of a file, the part of its metadata the verification looks at.
+#[derive(Debug, Clone, Copy)]
+struct FileOwnership {
+    uid: u32,
+    mode: u32,
+}
+
+impl From<&Metadata> for FileOwnership {
+    fn from(meta: &Metadata) -> Self {
+        Self {
+            uid: meta.uid(),
+            mode: meta.mode(),
+        }
+    }
+}
+
+fn check_socket_file(
+    uid: u32,
+    socket: FileOwnership,
+    dir: Option<FileOwnership>,
+) -> Result<(), PeerMismatch> {
+    if socket.uid != uid {
+        return Err(PeerMismatch::SocketOwner {
+            expected: uid,
+            actual: socket.uid,
+        });
+    }
+    if socket.mode & FOREIGN_ACCESS == 0 {
+        return Ok(());
+    }
+    let private_dir = dir.is_some_and(|dir| dir.uid == uid && dir.mode & FOREIGN_ACCESS == 0);
+    if private_dir {
+        Ok(())
+    } else {
+        Err(PeerMismatch::SocketMode(socket.mode & 0o7777))
+    }
+}
+
+/// Checks that the process on the other side of the connected `socket` runs as the current user.
+pub(crate) fn verify_peer<S: AsRawFd>(socket: &S) -> AgentResult<()> {
+    let uid = current_uid();
+    let peer = peer_uid(socket)?;
+    if peer != uid {
+        return Err(Error::PeerVerificationError(PeerMismatch::PeerUid {
+            expected: uid,
+            actual: peer,
+        }));
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid<S: AsRawFd>(socket: &S) -> io::Result<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: `cred` and `len` are valid for writes and `len` holds the size of `cred`.
+    let ret = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid<S: AsRawFd>(socket: &S) -> io::Result<u32> {
+    let mut uid = 0;
+    let mut gid = 0;
+    // SAFETY: `uid` and `gid` are valid for writes.
+    let ret = unsafe { libc::getpeereid(socket.as_raw_fd(), &mut uid, &mut gid) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::private_socket_dir;
+    use std::fs::{self, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    const UID: u32 = 1000;
+    const OTHER_UID: u32 = 1001;
+
+    /// Fake metadata of a socket or a directory.
+    fn file(uid: u32, mode: u32) -> FileOwnership {
+        FileOwnership { uid, mode }
+    }
+
+    #[test]
+    fn checks_socket_owner() {
+        let socket = file(OTHER_UID, 0o140600);
+        assert_eq!(
+            check_socket_file(UID, socket, Some(file(UID, 0o40700))),
+            Err(PeerMismatch::SocketOwner {
+                expected: UID,
+                actual: OTHER_UID
+            })
+        );
+    }
+
+    #[test]
+    fn checks_socket_mode() {
+        let private_socket = file(UID, 0o140600);
+        let shared_socket = file(UID, 0o140777);
+        assert_eq!(check_socket_file(UID, private_socket, None), Ok(()));
+        assert_eq!(
+            check_socket_file(UID, private_socket, Some(file(UID, 0o40755))),
+            Ok(())
+        );
+        assert_eq!(
+            check_socket_file(UID, shared_socket, Some(file(UID, 0o40700))),
+            Ok(())
+        );
+        for dir in [
+            Some(file(UID, 0o40755)),
+            Some(file(UID, 0o40710)),
+            Some(file(OTHER_UID, 0o40700)),
+            None,
+        ] {
+            assert_eq!(
+                check_socket_file(UID, shared_socket, dir),
+                Err(PeerMismatch::SocketMode(0o777)),
+                "{dir:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn verifies_socket_file() {
+        let dir = private_socket_dir().unwrap();
+        let path = dir.join("agent.sock");
+        let _listener = UnixListener::bind(&path).unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o777)).unwrap();
+        verify_socket_file(&path).unwrap();
+
+        fs::set_permissions(&dir, Permissions::from_mode(0o755)).unwrap();
+        assert!(matches!(
+            verify_socket_file(&path),
+            Err(Error::PeerVerificationError(PeerMismatch::SocketMode(
+                0o777
+            )))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn verifies_peer_uid() {
+        let (left, right) = UnixStream::pair().unwrap();
+        assert_eq!(peer_uid(&left).unwrap(), current_uid());
+        verify_peer(&left).unwrap();
+        verify_peer(&right).unwrap();
+    }
+}
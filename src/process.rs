@@ -0,0 +1,334 @@
+use std::ffi::OsStr;
+use std::fs::{self, DirBuilder, File};
+use std::io::{self, Read};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use crate::socket::SocketAddress;
+use crate::{AgentBuilder, AgentResult, Error};
+
+/// Name of the variable holding the agent PID in the startup output.
+const PID_VAR: &str = "OIDCD_PID";
+/// Name of the variable holding the agent socket in the startup output.
+const SOCKET_VAR: &str = "OIDC_SOCK";
+/// How many times the socket of a spawned agent is checked before giving up.
+const SOCKET_WAIT_ATTEMPTS: u32 = 100;
+/// Delay between two checks of the socket of a spawned agent.
+const SOCKET_WAIT_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How many random socket directory names are tried before giving up.
+const SOCKET_DIR_ATTEMPTS: u32 = 16;
+
+/// Agent socket and PID printed by `oidc-agent` or `oidc-agent-service` at startup.
+///
+/// The output is a shell snippet like:
+/// ```text
+/// OIDC_SOCK=/tmp/oidc-agent-service-1000/oidc-agent.sock; export OIDC_SOCK;
+/// OIDCD_PID=1234; export OIDCD_PID;
+/// echo Agent pid $OIDCD_PID
+/// ```
+///
+/// # Examples
+/// ```ignore
+/// let output = Command::new("oidc-agent-service").arg("use").output()?;
+/// let startup = AgentStartup::parse(&String::from_utf8_lossy(&output.stdout))?;
+/// let agent = startup.builder().timeout(Duration::from_secs(5)).build()?;
+/// println!("agent pid: {}", startup.pid());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentStartup {
+    socket: SocketAddress,
+    pid: u32,
+}
+
+impl AgentStartup {
+    /// Attempts to parse the startup output of the agent.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - the output does not set the `OIDC_SOCK` or the `OIDCD_PID` variable,
+    /// - the `OIDCD_PID` is not a valid PID of a single process, e.g. `0`.
+    pub fn parse(output: &str) -> AgentResult<Self> {
+        let mut socket = None;
+        let mut pid = None;
+        for statement in statements(output) {
+            let Some((name, value)) = statement.trim().split_once('=') else {
+                continue;
+            };
+            let value = unquote(value.trim());
+            let name = name.trim();
+            match name.strip_prefix("export ").map_or(name, str::trim_start) {
+                SOCKET_VAR => socket = Some(SocketAddress::from(value)),
+                PID_VAR => pid = Some(parse_pid(value)?),
+                _ => {}
+            }
+        }
+        match (socket, pid) {
+            (Some(socket), Some(pid)) => Ok(Self { socket, pid }),
+            (None, _) => Err("The agent output does not contain the OIDC_SOCK!".into()),
+            (_, None) => Err("The agent output does not contain the OIDCD_PID!".into()),
+        }
+    }
+
+    /// Returns the socket address of the agent.
+    pub fn socket(&self) -> &SocketAddress {
+        &self.socket
+    }
+
+    /// Returns the PID of the agent process.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Creates a new [`AgentBuilder`] with the socket address of the agent set.
+    pub fn builder(&self) -> AgentBuilder {
+        AgentBuilder::new().socket_address(self.socket.clone())
+    }
+}
+
+/// Parses the PID of the agent. `0` and the negative values address process groups when passed to
+/// `kill`, so they are rejected.
+fn parse_pid(value: &str) -> AgentResult<u32> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|pid| *pid != 0 && libc::pid_t::try_from(*pid).is_ok())
+        .ok_or_else(|| Error::OtherError(format!("Invalid agent pid: {}", value)))
+}
+
+/// Splits the shell snippet into the statements at the `;` and the line breaks outside of the
+/// quotes, so a quoted value may contain them.
+fn statements(output: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in output.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, ';' | '\n') => {
+                statements.push(&output[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&output[start..]);
+    statements
+}
+
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+        .unwrap_or(value)
+}
+
+/// Private agent process started by the [`crate::Agent::spawn`].
+///
+/// The agent is killed and its socket directory is removed when the `AgentProcess` is dropped.
+#[derive(Debug)]
+pub struct AgentProcess {
+    startup: AgentStartup,
+    socket_dir: PathBuf,
+}
+
+impl AgentProcess {
+    /// Attempts to start a new `oidc-agent` listening on a socket in a new private temporary
+    /// directory. The `args` are passed to the `oidc-agent`, the `--socket-path` is added.
+    /// # Errors
+    /// The method returns an coresponding [`Error`] if:
+    /// - creating the socket directory fails,
+    /// - the `oidc-agent` cannot be started or exits with an error,
+    /// - the agent output cannot be parsed,
+    /// - the agent socket does not appear in time.
+    pub(crate) fn spawn<I, S>(args: I) -> AgentResult<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let socket_dir = private_socket_dir()?;
+        let socket_path = socket_dir.join("oidc-agent.sock");
+
+        let output = Command::new("oidc-agent")
+            .args(args)
+            .arg(format!("--socket-path={}", socket_path.display()))
+            .output();
+        let startup = output.map_err(Error::from).and_then(|output| {
+            if !output.status.success() {
+                return Err(Error::OtherError(format!(
+                    "oidc-agent failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            AgentStartup::parse(&String::from_utf8_lossy(&output.stdout))
+        });
+        let startup = match startup {
+            Ok(startup) => startup,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&socket_dir);
+                return Err(e);
+            }
+        };
+        let process = Self {
+            startup,
+            socket_dir,
+        };
+        process.wait_for_socket(&socket_path)?;
+        Ok(process)
+    }
+
+    fn wait_for_socket(&self, socket_path: &Path) -> AgentResult<()> {
+        for _ in 0..SOCKET_WAIT_ATTEMPTS {
+            if socket_path
+                .metadata()
+                .is_ok_and(|meta| meta.file_type().is_socket())
+            {
+                return Ok(());
+            }
+            thread::sleep(SOCKET_WAIT_INTERVAL);
+        }
+        Err("The spawned agent did not create its socket!".into())
+    }
+
+    /// Returns the socket address and the PID of the agent.
+    pub fn startup(&self) -> &AgentStartup {
+        &self.startup
+    }
+
+    /// Returns the PID of the agent process.
+    pub fn pid(&self) -> u32 {
+        self.startup.pid
+    }
+}
+
+/// Creates a new temporary directory accessible only by the current user for an agent socket.
+/// The directory name is random, so other users cannot occupy it in advance. An existing
+/// directory is never reused.
+pub(crate) fn private_socket_dir() -> io::Result<PathBuf> {
+    let mut attempts = 1;
+    loop {
+        let socket_dir = std::env::temp_dir().join(format!("oidc-agent-rs-{:016x}", random_u64()?));
+        match DirBuilder::new().mode(0o700).create(&socket_dir) {
+            Err(e)
+                if e.kind() == io::ErrorKind::AlreadyExists && attempts < SOCKET_DIR_ATTEMPTS =>
+            {
+                attempts += 1;
+            }
+            result => return result.map(|()| socket_dir),
+        }
+    }
+}
+
+fn random_u64() -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(u64::from_ne_bytes(bytes))
+}
+
+impl Drop for AgentProcess {
+    fn drop(&mut self) {
+        if let Ok(pid @ 1..) = libc::pid_t::try_from(self.startup.pid) {
+            // SAFETY: kill has no memory safety requirements.
+            unsafe { libc::kill(pid, libc::SIGTERM) };
+        }
+        let _ = fs::remove_dir_all(&self.socket_dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    fn socket_path(startup: &AgentStartup) -> &Path {
+        startup.socket().as_path().unwrap()
+    }
+
+    #[test]
+    fn parses_agent_output() {
+        let startup = AgentStartup::parse(
+            "OIDC_SOCK=/tmp/oidc-agent-service-1000/oidc-agent.sock; export OIDC_SOCK;\n\
+             OIDCD_PID=1234; export OIDCD_PID;\n\
+             echo Agent pid $OIDCD_PID\n",
+        )
+        .unwrap();
+        assert_eq!(
+            socket_path(&startup),
+            Path::new("/tmp/oidc-agent-service-1000/oidc-agent.sock")
+        );
+        assert_eq!(startup.pid(), 1234);
+    }
+
+    #[test]
+    fn parses_quoted_values() {
+        let startup =
+            AgentStartup::parse("OIDC_SOCK=\"/tmp/with space/oidc.sock\"; OIDCD_PID='42';")
+                .unwrap();
+        assert_eq!(
+            socket_path(&startup),
+            Path::new("/tmp/with space/oidc.sock")
+        );
+        assert_eq!(startup.pid(), 42);
+    }
+
+    #[test]
+    fn parses_quoted_separators() {
+        let startup = AgentStartup::parse(
+            "OIDC_SOCK=\"/tmp/a;b\nc/oidc.sock\"; export OIDC_SOCK;\n\
+             OIDCD_PID='42'; export OIDCD_PID;\n",
+        )
+        .unwrap();
+        assert_eq!(socket_path(&startup), Path::new("/tmp/a;b\nc/oidc.sock"));
+        assert_eq!(startup.pid(), 42);
+
+        let startup =
+            AgentStartup::parse("OIDC_SOCK='/tmp/say \"hi\";/oidc.sock'; OIDCD_PID=7").unwrap();
+        assert_eq!(
+            socket_path(&startup),
+            Path::new("/tmp/say \"hi\";/oidc.sock")
+        );
+    }
+
+    #[test]
+    fn creates_private_socket_dirs() {
+        let first = private_socket_dir().unwrap();
+        let second = private_socket_dir().unwrap();
+        assert_ne!(first, second);
+        for dir in [first, second] {
+            assert_eq!(dir.metadata().unwrap().mode() & 0o777, 0o700);
+            fs::remove_dir(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn parses_export_form() {
+        let startup =
+            AgentStartup::parse("export OIDC_SOCK=/tmp/oidc.sock\nexport OIDCD_PID=7\n").unwrap();
+        assert_eq!(socket_path(&startup), Path::new("/tmp/oidc.sock"));
+        assert_eq!(startup.pid(), 7);
+    }
+
+    #[test]
+    fn rejects_missing_variables() {
+        let missing_pid = AgentStartup::parse("OIDC_SOCK=/tmp/oidc.sock; export OIDC_SOCK;");
+        assert!(missing_pid.unwrap_err().to_string().contains("OIDCD_PID"));
+        let missing_socket = AgentStartup::parse("OIDCD_PID=1234; export OIDCD_PID;");
+        assert!(
+            missing_socket
+                .unwrap_err()
+                .to_string()
+                .contains("OIDC_SOCK")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_pids() {
+        for pid in ["0", "-1", "abc", "", "4294967295"] {
+            let output = format!("OIDC_SOCK=/tmp/oidc.sock; OIDCD_PID={};", pid);
+            assert!(AgentStartup::parse(&output).is_err(), "{pid}");
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use chrono::{DateTime, Utc};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use url::Url;
+
+use crate::requests::AccessTokenRequest;
+use crate::responses::AccessTokenResponse;
+use crate::transport::Transport;
+use crate::{Agent, AgentResult, Token};
+
+/// Source of the tokens, so the application code does not depend on a concrete [`Agent`].
+///
+/// Implemented by the [`Agent`], the [`StaticTokenProvider`], the [`SourceTokenProvider`], the
+/// [`crate::CachedAgent`] and the `MockAgent` of the `testing` feature. See the [`crate::async_impl::AsyncTokenProvider`] for the
+/// asynchronous version.
+///
+/// # Examples
+/// ```ignore
+/// fn authorization_header(provider: &impl TokenProvider) -> AgentResult<String> {
+///     let token = provider.fetch_access_token("profile_shortname")?;
+///     Ok(format!("Bearer {}", token.secret()))
+/// }
+/// ```
+pub trait TokenProvider {
+    /// Attempts to get the access token of the account.
+    /// # Errors
+    /// The method returns an coresponding [`crate::Error`] if the token cannot be provided.
+    fn fetch_access_token(&self, account_shortname: &str) -> AgentResult<Token> {
+        let request = AccessTokenRequest::basic(account_shortname);
+        let response = self.fetch_access_token_response(request)?;
+        Ok(response.access_token().clone())
+    }
+
+    /// Attempts to get the full access token response to the `request`.
+    /// # Errors
+    /// The method returns an coresponding [`crate::Error`] if the token cannot be provided.
+    fn fetch_access_token_response(
+        &self,
+        request: AccessTokenRequest,
+    ) -> AgentResult<AccessTokenResponse>;
+
+    /// Attempts to get the mytoken of the account.
+    /// # Errors
+    /// The method returns an coresponding [`crate::Error`] if the token cannot be provided.
+    fn fetch_mytoken(&self, account_shortname: &str) -> AgentResult<Token>;
+}
+
+impl<T: Transport> TokenProvider for Agent<T> {
+    fn fetch_access_token_response(
+        &self,
+        request: AccessTokenRequest,
+    ) -> AgentResult<AccessTokenResponse> {
+        self.send_request(request)
+    }
+
+    fn fetch_mytoken(&self, account_shortname: &str) -> AgentResult<Token> {
+        self.get_mytoken(account_shortname)
+    }
+}
+
+/// Provider returning the same tokens for every account and request, e.g. a token passed to the
+/// application in its configuration.
+///
+/// # Examples
+/// ```ignore
+/// let provider = StaticTokenProvider::new(token, "https://issuer.url")?.mytoken(mytoken);
+/// ```
+#[derive(Debug, Clone)]
+pub struct StaticTokenProvider {
+    access_token: Token,
+    issuer: Url,
+    expires_at: DateTime<Utc>,
+    mytoken: Option<Token>,
+}
+
+impl StaticTokenProvider {
+    /// Attempts to create a new provider of the `access_token` issued by the `issuer`. The token
+    /// never expires unless the [`StaticTokenProvider::expires_at`] is set.
+    /// # Errors
+    /// The method returns an coresponding [`crate::Error`] if:
+    /// - parsing the `issuer` as an url fails.
+    pub fn new<T: ToString>(access_token: T, issuer: &str) -> AgentResult<Self> {
+        Ok(Self {
+            access_token: Token(access_token.to_string()),
+            issuer: Url::parse(issuer)?,
+            expires_at: DateTime::<Utc>::MAX_UTC,
+            mytoken: None,
+        })
+    }
+    /// Sets the expiration time reported in the access token response.
+    pub fn expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+    /// Sets the mytoken. Without it, requesting the mytoken fails.
+    pub fn mytoken<T: ToString>(mut self, mytoken: T) -> Self {
+        self.mytoken = Some(Token(mytoken.to_string()));
+        self
+    }
+
+    fn access_token_response(&self) -> AccessTokenResponse {
+        AccessTokenResponse::new(
+            self.access_token.clone(),
+            self.issuer.clone(),
+            self.expires_at,
+        )
+    }
+
+    fn mytoken_token(&self) -> AgentResult<Token> {
+        self.mytoken
+            .clone()
+            .ok_or_else(|| "The provider has no mytoken!".into())
+    }
+}
+
+impl TokenProvider for StaticTokenProvider {
+    fn fetch_access_token_response(
+        &self,
+        _request: AccessTokenRequest,
+    ) -> AgentResult<AccessTokenResponse> {
+        Ok(self.access_token_response())
+    }
+
+    fn fetch_mytoken(&self, _account_shortname: &str) -> AgentResult<Token> {
+        self.mytoken_token()
+    }
+}
+
+/// Place a token is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenSource {
+    /// The environment variable with the given name.
+    EnvVar(String),
+    /// The file at the given path, e.g. a token mounted into a container. Leading and trailing
+    /// whitespace is trimmed.
+    File(PathBuf),
+}
+
+impl TokenSource {
+    /// Attempts to read the token.
+    /// # Errors
+    /// The method returns an coresponding [`crate::Error`] if:
+    /// - the environment variable is not set or cannot be retrieved,
+    /// - the file cannot be read.
+    pub fn read(&self) -> AgentResult<Token> {
+        let token = match self {
+            Self::EnvVar(name) => env::var(name)?,
+            Self::File(path) => fs::read_to_string(path)?,
+        };
+        Ok(Token(token.trim().to_string()))
+    }
+}
+
+/// Provider reading the tokens from the environment variables or files on every request, so
+/// rotated tokens are picked up. The same tokens are returned for every account.
+///
+/// The expiration time of the tokens is unknown, the access token response reports that they
+/// never expire.
+///
+/// # Examples
+/// ```ignore
+/// let provider = SourceTokenProvider::new(
+///     TokenSource::File("/var/run/secrets/token".into()),
+///     "https://issuer.url",
+/// )?
+/// .mytoken(TokenSource::EnvVar("MYTOKEN".to_string()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SourceTokenProvider {
+    access_token: TokenSource,
+    issuer: Url,
+    mytoken: Option<TokenSource>,
+}
+
+impl SourceTokenProvider {
+    /// Attempts to create a new provider reading the access token issued by the `issuer` from the
+    /// `access_token` source.
+    /// # Errors
+    /// The method returns an coresponding [`crate::Error`] if:
+    /// - parsing the `issuer` as an url fails.
+    pub fn new(access_token: TokenSource, issuer: &str) -> AgentResult<Self> {
+        Ok(Self {
+            access_token,
+            issuer: Url::parse(issuer)?,
+            mytoken: None,
+        })
+    }
+    /// Sets the source of the mytoken. Without it, requesting the mytoken fails.
+    pub fn mytoken(mut self, mytoken: TokenSource) -> Self {
+        self.mytoken = Some(mytoken);
+        self
+    }
+
+    fn access_token_response(&self) -> AgentResult<AccessTokenResponse> {
+        Ok(AccessTokenResponse::new(
+            self.access_token.read()?,
+            self.issuer.clone(),
+            DateTime::<Utc>::MAX_UTC,
+        ))
+    }
+
+    fn mytoken_token(&self) -> AgentResult<Token> {
+        match &self.mytoken {
+            Some(source) => source.read(),
+            None => Err("The provider has no mytoken source!".into()),
+        }
+    }
+}
+
+impl TokenProvider for SourceTokenProvider {
+    fn fetch_access_token_response(
+        &self,
+        _request: AccessTokenRequest,
+    ) -> AgentResult<AccessTokenResponse> {
+        self.access_token_response()
+    }
+
+    fn fetch_mytoken(&self, _account_shortname: &str) -> AgentResult<Token> {
+        self.mytoken_token()
+    }
+}
+
+#[cfg(feature = "testing")]
+impl TokenProvider for crate::testing::MockAgent {
+    fn fetch_access_token_response(
+        &self,
+        request: AccessTokenRequest,
+    ) -> AgentResult<AccessTokenResponse> {
+        self.provider_agent().fetch_access_token_response(request)
+    }
+
+    fn fetch_mytoken(&self, account_shortname: &str) -> AgentResult<Token> {
+        self.provider_agent().fetch_mytoken(account_shortname)
+    }
+}
+
+#[cfg(any(feature = "async", feature = "smol"))]
+mod async_providers {
+    use super::{SourceTokenProvider, StaticTokenProvider};
+    use crate::async_impl::AsyncTokenProvider;
+    use crate::requests::AccessTokenRequest;
+    use crate::responses::AccessTokenResponse;
+    use crate::{AgentResult, Token};
+
+    impl AsyncTokenProvider for StaticTokenProvider {
+        async fn fetch_access_token_response(
+            &self,
+            _request: AccessTokenRequest,
+        ) -> AgentResult<AccessTokenResponse> {
+            Ok(self.access_token_response())
+        }
+
+        async fn fetch_mytoken(&self, _account_shortname: &str) -> AgentResult<Token> {
+            self.mytoken_token()
+        }
+    }
+
+    /// The sources are read with the blocking IO, reading an environment variable or a small file
+    /// does not stall the runtime noticeably.
+    impl AsyncTokenProvider for SourceTokenProvider {
+        async fn fetch_access_token_response(
+            &self,
+            _request: AccessTokenRequest,
+        ) -> AgentResult<AccessTokenResponse> {
+            self.access_token_response()
+        }
+
+        async fn fetch_mytoken(&self, _account_shortname: &str) -> AgentResult<Token> {
+            self.mytoken_token()
+        }
+    }
+
+    /// The mock is served over the tokio runtime of the `async` feature. With the `smol` runtime
+    /// build the agent with the `MockAgent::builder` and the `AgentBuilder::build_smol` instead.
+    #[cfg(all(feature = "testing", feature = "async"))]
+    impl AsyncTokenProvider for crate::testing::MockAgent {
+        async fn fetch_access_token_response(
+            &self,
+            request: AccessTokenRequest,
+        ) -> AgentResult<AccessTokenResponse> {
+            self.async_provider_agent()
+                .fetch_access_token_response(request)
+                .await
+        }
+
+        async fn fetch_mytoken(&self, account_shortname: &str) -> AgentResult<Token> {
+            self.async_provider_agent()
+                .fetch_mytoken(account_shortname)
+                .await
+        }
+    }
+}
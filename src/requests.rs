@@ -1,18 +1,41 @@
 use crate::{
+    generate::Flow,
     mytoken::Profile,
-    responses::{AccessTokenResponse, AccountsResponse, MyTokenResponse},
-    AgentResult, Request,
+    responses::{
+        AccessTokenResponse, AccountInfoResponse, AccountsResponse, BasicResponse,
+        DeviceAuthorization, GenResponse, IdTokenResponse, MyTokenResponse, RegisterResponse,
+        ScopesResponse, StatusResponse,
+    },
+    AccountConfig, AgentResult, Request, Response, Token,
 };
 use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
 use url::Url;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
-#[allow(non_camel_case_types)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 pub(crate) enum RequestType {
     ACCESS_TOKEN,
+    ID_TOKEN,
     MYTOKEN,
     LOADED_ACCOUNTS,
+    ACCOUNT_INFO,
+    LOCK,
+    UNLOCK,
+    ADD,
+    REMOVE,
+    REMOVE_ALL,
+    DELETE,
+    CHECK,
+    GEN,
+    #[serde(rename = "device")]
+    DEVICE_LOOKUP,
+    CODE_EXCHANGE,
+    STATE_LOOKUP,
+    REGISTER,
+    REAUTHENTICATE,
+    SCOPES,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -64,10 +87,92 @@ impl AccessTokenRequest {
             audience: None,
         })
     }
+
+    pub(crate) fn account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
+    pub(crate) fn issuer(&self) -> Option<&Url> {
+        self.issuer.as_ref()
+    }
+
+    pub(crate) fn scope(&self) -> Option<&str> {
+        self.scope.as_deref()
+    }
+
+    pub(crate) fn audience(&self) -> Option<&str> {
+        self.audience.as_deref()
+    }
+
+    pub(crate) fn min_valid_period(&self) -> Option<u64> {
+        self.min_valid_period
+    }
+
+    pub(crate) fn set_min_valid_period(&mut self, min_valid_period: u64) {
+        self.min_valid_period = Some(min_valid_period);
+    }
 }
 
 impl Request for AccessTokenRequest {
     type SuccessResponse = AccessTokenResponse;
+
+    fn application_hint_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.application_hint)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdTokenRequest {
+    request: RequestType,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issuer: Option<Url>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    application_hint: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audience: Option<String>,
+}
+
+impl IdTokenRequest {
+    /// Creates new request with only `account` field set.
+    pub fn basic(account: &str) -> Self {
+        Self {
+            request: RequestType::ID_TOKEN,
+            account: Some(account.to_string()),
+            issuer: None,
+            application_hint: None,
+            scope: None,
+            audience: None,
+        }
+    }
+
+    /// Creates a new `IdTokenRequestBuilder` to build a request.
+    pub fn builder() -> IdTokenRequestBuilder {
+        IdTokenRequestBuilder(Self {
+            request: RequestType::ID_TOKEN,
+            account: None,
+            issuer: None,
+            application_hint: None,
+            scope: None,
+            audience: None,
+        })
+    }
+}
+
+impl Request for IdTokenRequest {
+    type SuccessResponse = IdTokenResponse;
+
+    fn application_hint_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.application_hint)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -100,6 +205,10 @@ impl MyTokenRequest {
 
 impl Request for MyTokenRequest {
     type SuccessResponse = MyTokenResponse;
+
+    fn application_hint_mut(&mut self) -> Option<&mut Option<String>> {
+        Some(&mut self.application_hint)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -116,10 +225,398 @@ impl AccountsRequest {
     }
 }
 
+impl Default for AccountsRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Request for AccountsRequest {
     type SuccessResponse = AccountsResponse;
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountInfoRequest {
+    request: RequestType,
+}
+
+impl AccountInfoRequest {
+    ///Creates a new request.
+    pub fn new() -> Self {
+        Self {
+            request: RequestType::ACCOUNT_INFO,
+        }
+    }
+}
+
+impl Default for AccountInfoRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Request for AccountInfoRequest {
+    type SuccessResponse = AccountInfoResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockRequest {
+    request: RequestType,
+    password: Token,
+}
+
+impl LockRequest {
+    ///Creates a new request locking the agent with the `password`.
+    pub fn new(password: &str) -> Self {
+        Self {
+            request: RequestType::LOCK,
+            password: Token(password.to_string()),
+        }
+    }
+}
+
+impl Request for LockRequest {
+    type SuccessResponse = BasicResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnlockRequest {
+    request: RequestType,
+    password: Token,
+}
+
+impl UnlockRequest {
+    ///Creates a new request unlocking the agent with the `password`.
+    pub fn new(password: &str) -> Self {
+        Self {
+            request: RequestType::UNLOCK,
+            password: Token(password.to_string()),
+        }
+    }
+}
+
+impl Request for UnlockRequest {
+    type SuccessResponse = BasicResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AddRequest {
+    request: RequestType,
+    config: AccountConfig,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lifetime: Option<u64>, //Always seconds
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    confirm: bool,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    alwaysallowidtoken: bool,
+}
+
+impl AddRequest {
+    /// Creates new request with only `config` field set.
+    pub fn basic(config: &AccountConfig) -> Self {
+        Self {
+            request: RequestType::ADD,
+            config: config.clone(),
+            lifetime: None,
+            confirm: false,
+            alwaysallowidtoken: false,
+        }
+    }
+    /// Creates a new `AddRequestBuilder` to build a request.
+    pub fn builder(config: &AccountConfig) -> AddRequestBuilder {
+        AddRequestBuilder(AddRequest::basic(config))
+    }
+}
+
+impl Request for AddRequest {
+    type SuccessResponse = BasicResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemoveRequest {
+    request: RequestType,
+    account: String,
+}
+
+impl RemoveRequest {
+    ///Creates a new request unloading the `account`.
+    pub fn new(account: &str) -> Self {
+        Self {
+            request: RequestType::REMOVE,
+            account: account.to_string(),
+        }
+    }
+}
+
+impl Request for RemoveRequest {
+    type SuccessResponse = BasicResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemoveAllRequest {
+    request: RequestType,
+}
+
+impl RemoveAllRequest {
+    ///Creates a new request.
+    pub fn new() -> Self {
+        Self {
+            request: RequestType::REMOVE_ALL,
+        }
+    }
+}
+
+impl Default for RemoveAllRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Request for RemoveAllRequest {
+    type SuccessResponse = BasicResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteRequest {
+    request: RequestType,
+    config: AccountConfig,
+}
+
+impl DeleteRequest {
+    ///Creates a new request revoking the tokens of the account at its issuer. The agent needs the
+    ///decrypted account `config`, it holds the refresh token and the client credentials.
+    pub fn new(config: &AccountConfig) -> Self {
+        Self {
+            request: RequestType::DELETE,
+            config: config.clone(),
+        }
+    }
+}
+
+impl Request for DeleteRequest {
+    type SuccessResponse = BasicResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheckRequest {
+    request: RequestType,
+}
+
+impl CheckRequest {
+    ///Creates a new request.
+    pub fn new() -> Self {
+        Self {
+            request: RequestType::CHECK,
+        }
+    }
+}
+
+impl Default for CheckRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Request for CheckRequest {
+    type SuccessResponse = StatusResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GenRequest {
+    request: RequestType,
+    config: AccountConfig,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    flow: Vec<Flow>,
+}
+
+impl GenRequest {
+    /// Creates a new `GenRequestBuilder` to build a request generating the `account`.
+    pub fn builder(account: &str) -> GenRequestBuilder {
+        let mut config = AccountConfig::empty();
+        config.set("name", account);
+        GenRequestBuilder(Self {
+            request: RequestType::GEN,
+            config,
+            flow: Vec::new(),
+        })
+    }
+}
+
+impl Request for GenRequest {
+    type SuccessResponse = GenResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeviceLookupRequest {
+    request: RequestType,
+    config: AccountConfig,
+    oidc_device: DeviceAuthorization,
+}
+
+impl DeviceLookupRequest {
+    ///Creates a new request checking whether the user authorized the `device`.
+    pub fn new(config: &AccountConfig, device: &DeviceAuthorization) -> Self {
+        Self {
+            request: RequestType::DEVICE_LOOKUP,
+            config: config.clone(),
+            oidc_device: device.clone(),
+        }
+    }
+}
+
+impl Request for DeviceLookupRequest {
+    type SuccessResponse = GenResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CodeExchangeRequest {
+    request: RequestType,
+    redirected_uri: Url,
+    state: String,
+}
+
+impl CodeExchangeRequest {
+    ///Creates a new request exchanging the authorization code contained in the `redirected_uri`.
+    pub fn new(redirected_uri: &Url, state: &str) -> Self {
+        Self {
+            request: RequestType::CODE_EXCHANGE,
+            redirected_uri: redirected_uri.clone(),
+            state: state.to_string(),
+        }
+    }
+}
+
+impl Request for CodeExchangeRequest {
+    type SuccessResponse = GenResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StateLookupRequest {
+    request: RequestType,
+    state: String,
+}
+
+impl StateLookupRequest {
+    ///Creates a new request looking up the result of the flow identified by the `state`.
+    pub fn new(state: &str) -> Self {
+        Self {
+            request: RequestType::STATE_LOOKUP,
+            state: state.to_string(),
+        }
+    }
+}
+
+impl Request for StateLookupRequest {
+    type SuccessResponse = GenResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegisterRequest {
+    request: RequestType,
+    config: AccountConfig,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    flow: Vec<Flow>,
+}
+
+impl RegisterRequest {
+    /// Creates a new `RegisterRequestBuilder` to build a request.
+    pub fn builder() -> RegisterRequestBuilder {
+        RegisterRequestBuilder(Self {
+            request: RequestType::REGISTER,
+            config: AccountConfig::empty(),
+            flow: Vec::new(),
+        })
+    }
+}
+
+impl Request for RegisterRequest {
+    type SuccessResponse = RegisterResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReauthenticateRequest {
+    request: RequestType,
+    account: String,
+}
+
+impl ReauthenticateRequest {
+    ///Creates a new request obtaining a new refresh token for the `account`.
+    pub fn new(account: &str) -> Self {
+        Self {
+            request: RequestType::REAUTHENTICATE,
+            account: account.to_string(),
+        }
+    }
+}
+
+impl Request for ReauthenticateRequest {
+    type SuccessResponse = GenResponse;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScopesRequest {
+    request: RequestType,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account: Option<String>,
+
+    #[serde(rename = "issuer_url", skip_serializing_if = "Option::is_none")]
+    issuer: Option<Url>,
+}
+
+impl ScopesRequest {
+    /// Creates a new `ScopesRequestBuilder` to build a request.
+    pub fn builder() -> ScopesRequestBuilder {
+        ScopesRequestBuilder(Self {
+            request: RequestType::SCOPES,
+            account: None,
+            issuer: None,
+        })
+    }
+}
+
+impl Request for ScopesRequest {
+    type SuccessResponse = ScopesResponse;
+}
+
+/// Request with an arbitrary JSON body. It allows sending IPC requests that are not wrapped by
+/// this crate yet, while still getting the agent status and error handling of
+/// [`crate::Agent::send_request`]. The response is deserialized as `R`, which can be
+/// [`serde_json::Value`] or any custom [`Response`].
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct GenericRequest<R: Response> {
+    body: serde_json::Value,
+
+    #[serde(skip)]
+    response: PhantomData<fn() -> R>,
+}
+
+impl<R: Response> GenericRequest<R> {
+    ///Attempts to create a new request with the given JSON `body`.
+    ///# Errors
+    ///The method returns an coresponding [`crate::Error`] if:
+    ///- `body` is not a JSON object with the `request` string field.
+    pub fn new(body: serde_json::Value) -> AgentResult<Self> {
+        if !body.get("request").is_some_and(|r| r.is_string()) {
+            return Err("Failed to build request! JSON object with request field required!".into());
+        }
+        Ok(Self {
+            body,
+            response: PhantomData,
+        })
+    }
+}
+
+impl<R: Response> Request for GenericRequest<R> {
+    type SuccessResponse = R;
+}
+
 pub struct AccessTokenRequestBuilder(AccessTokenRequest);
 
 impl AccessTokenRequestBuilder {
@@ -150,7 +647,7 @@ impl AccessTokenRequestBuilder {
     ///Add a new scope to the target request. It can take mulitple scopes that should be space separated.
     pub fn add_scope<T: ToString>(mut self, scope: T) -> Self {
         if let Some(ref mut curr_scope) = self.0.scope {
-            curr_scope.push_str(" ");
+            curr_scope.push(' ');
             curr_scope.push_str(&scope.to_string());
         } else {
             self.0.scope = Some(scope.to_string().trim().to_string());
@@ -182,6 +679,63 @@ impl AccessTokenRequestBuilder {
     }
 }
 
+pub struct IdTokenRequestBuilder(IdTokenRequest);
+
+impl IdTokenRequestBuilder {
+    ///Sets the `account` for the target request.
+    pub fn account<T: ToString>(mut self, account: T) -> Self {
+        self.0.account = Some(account.to_string());
+        self
+    }
+    ///Attempts to set the `issuer` for the target request.
+    ///# Errors
+    ///the method returns an coresponding [`crate::Error`] if:
+    ///- parsing the issuer as an url fails.
+    pub fn issuer(mut self, issuer: &str) -> AgentResult<Self> {
+        let iss = Url::parse(issuer)?;
+        self.0.issuer = Some(iss);
+        Ok(self)
+    }
+    ///Sets the `application_hint` for the target request.
+    pub fn application_hint<T: ToString>(mut self, application_hint: T) -> Self {
+        self.0.application_hint = Some(application_hint.to_string());
+        self
+    }
+    ///Add a new scope to the target request. It can take mulitple scopes that should be space separated.
+    pub fn add_scope<T: ToString>(mut self, scope: T) -> Self {
+        if let Some(ref mut curr_scope) = self.0.scope {
+            curr_scope.push(' ');
+            curr_scope.push_str(&scope.to_string());
+        } else {
+            self.0.scope = Some(scope.to_string().trim().to_string());
+        }
+        self
+    }
+    ///Sets the `audience` for the target request.
+    pub fn audience<T: ToString>(mut self, audience: T) -> Self {
+        self.0.audience = Some(audience.to_string());
+        self
+    }
+    ///Attempts to build the target request from the builder.
+    ///# Errors
+    ///The method returns an coresponding [`crate::Error`] if:
+    ///- neither `account` nor `issuer` is set.
+    ///- `account` consists only of withespaces.
+    pub fn build(self) -> AgentResult<IdTokenRequest> {
+        if self
+            .0
+            .account
+            .as_ref()
+            .is_some_and(|a| !a.trim().is_empty())
+            || self.0.issuer.is_some()
+        {
+            Ok(self.0)
+        } else {
+            Err("Failed to build request! Account name or issuer required!".into())
+        }
+    }
+}
+
 pub struct MyTokenRequestBuilder(MyTokenRequest);
 
 impl MyTokenRequestBuilder {
@@ -206,3 +760,187 @@ impl MyTokenRequestBuilder {
         Ok(self.0)
     }
 }
+
+pub struct AddRequestBuilder(AddRequest);
+
+impl AddRequestBuilder {
+    ///Sets the `lifetime` (in seconds) after which the agent unloads the account.
+    pub fn lifetime(mut self, lifetime: u64) -> Self {
+        self.0.lifetime = Some(lifetime);
+        self
+    }
+    ///Requires the user to confirm every usage of the account.
+    pub fn confirm(mut self) -> Self {
+        self.0.confirm = true;
+        self
+    }
+    ///Allows the agent to hand out id_tokens for the account without asking the user.
+    pub fn always_allow_id_token(mut self) -> Self {
+        self.0.alwaysallowidtoken = true;
+        self
+    }
+    ///Attempts to build the target request from the builder.
+    ///# Errors
+    ///The method returns an coresponding [`crate::Error`] if:
+    ///- the `config` has no account shortname.
+    pub fn build(self) -> AgentResult<AddRequest> {
+        if self
+            .0
+            .config
+            .shortname()
+            .is_none_or(|name| name.trim().is_empty())
+        {
+            return Err("Failed to build request! Account config has no shortname!".into());
+        }
+        Ok(self.0)
+    }
+}
+
+pub struct GenRequestBuilder(GenRequest);
+
+impl GenRequestBuilder {
+    ///Attempts to set the `issuer` for the generated account.
+    ///# Errors
+    ///the method returns an coresponding [`crate::Error`] if:
+    ///- parsing the issuer as an url fails.
+    pub fn issuer(mut self, issuer: &str) -> AgentResult<Self> {
+        let iss = Url::parse(issuer)?;
+        self.0.config.set("issuer_url", iss.as_str());
+        Ok(self)
+    }
+    ///Sets the `client_id` of an already registered client. If not set, the agent attempts to
+    ///register a new client dynamically.
+    pub fn client_id<T: ToString>(mut self, client_id: T) -> Self {
+        self.0.config.set("client_id", client_id.to_string());
+        self
+    }
+    ///Sets the `client_secret` of an already registered client.
+    pub fn client_secret<T: ToString>(mut self, client_secret: T) -> Self {
+        self.0
+            .config
+            .set("client_secret", client_secret.to_string());
+        self
+    }
+    ///Add a new scope to the generated account. It can take mulitple scopes that should be space separated.
+    pub fn add_scope<T: ToString>(mut self, scope: T) -> Self {
+        self.0.config.add_scope(&scope.to_string());
+        self
+    }
+    ///Attempts to add a new redirect uri for the authorization code flow.
+    ///# Errors
+    ///the method returns an coresponding [`crate::Error`] if:
+    ///- parsing the redirect uri as an url fails.
+    pub fn add_redirect_uri(mut self, redirect_uri: &str) -> AgentResult<Self> {
+        let uri = Url::parse(redirect_uri)?;
+        self.0.config.add_redirect_uri(&uri);
+        Ok(self)
+    }
+    ///Add a new flow the agent is allowed to use. The flows are tried in the order they were added.
+    pub fn add_flow(mut self, flow: Flow) -> Self {
+        if !self.0.flow.contains(&flow) {
+            self.0.flow.push(flow);
+        }
+        self
+    }
+    ///Attempts to build the target request from the builder.
+    ///# Errors
+    ///The method returns an coresponding [`crate::Error`] if:
+    ///- `account` consists only of withespaces.
+    ///- `issuer` is not set.
+    pub fn build(self) -> AgentResult<GenRequest> {
+        if self
+            .0
+            .config
+            .shortname()
+            .is_none_or(|name| name.trim().is_empty())
+        {
+            return Err("Failed to build request! Account name cannot be empty!".into());
+        }
+        if self.0.config.issuer().is_none() {
+            return Err("Failed to build request! Issuer required!".into());
+        }
+        Ok(self.0)
+    }
+}
+
+pub struct RegisterRequestBuilder(RegisterRequest);
+
+impl RegisterRequestBuilder {
+    ///Attempts to set the `issuer` the client is registered at.
+    ///# Errors
+    ///the method returns an coresponding [`crate::Error`] if:
+    ///- parsing the issuer as an url fails.
+    pub fn issuer(mut self, issuer: &str) -> AgentResult<Self> {
+        let iss = Url::parse(issuer)?;
+        self.0.config.set("issuer_url", iss.as_str());
+        Ok(self)
+    }
+    ///Attempts to add a new redirect uri for the registered client.
+    ///# Errors
+    ///the method returns an coresponding [`crate::Error`] if:
+    ///- parsing the redirect uri as an url fails.
+    pub fn add_redirect_uri(mut self, redirect_uri: &str) -> AgentResult<Self> {
+        let uri = Url::parse(redirect_uri)?;
+        self.0.config.add_redirect_uri(&uri);
+        Ok(self)
+    }
+    ///Add a new scope requested for the registered client. It can take mulitple scopes that should be space separated.
+    pub fn add_scope<T: ToString>(mut self, scope: T) -> Self {
+        self.0.config.add_scope(&scope.to_string());
+        self
+    }
+    ///Add a new flow the registered client should support.
+    pub fn add_flow(mut self, flow: Flow) -> Self {
+        if !self.0.flow.contains(&flow) {
+            self.0.flow.push(flow);
+        }
+        self
+    }
+    ///Attempts to build the target request from the builder.
+    ///# Errors
+    ///The method returns an coresponding [`crate::Error`] if:
+    ///- `issuer` is not set.
+    pub fn build(self) -> AgentResult<RegisterRequest> {
+        if self.0.config.issuer().is_none() {
+            return Err("Failed to build request! Issuer required!".into());
+        }
+        Ok(self.0)
+    }
+}
+
+pub struct ScopesRequestBuilder(ScopesRequest);
+
+impl ScopesRequestBuilder {
+    ///Sets the loaded `account` whose issuer is queried.
+    pub fn account<T: ToString>(mut self, account: T) -> Self {
+        self.0.account = Some(account.to_string());
+        self
+    }
+    ///Attempts to set the `issuer` that is queried.
+    ///# Errors
+    ///the method returns an coresponding [`crate::Error`] if:
+    ///- parsing the issuer as an url fails.
+    pub fn issuer(mut self, issuer: &str) -> AgentResult<Self> {
+        let iss = Url::parse(issuer)?;
+        self.0.issuer = Some(iss);
+        Ok(self)
+    }
+    ///Attempts to build the target request from the builder.
+    ///# Errors
+    ///The method returns an coresponding [`crate::Error`] if:
+    ///- neither `account` nor `issuer` is set.
+    ///- `account` consists only of withespaces.
+    pub fn build(self) -> AgentResult<ScopesRequest> {
+        if self
+            .0
+            .account
+            .as_ref()
+            .is_some_and(|a| !a.trim().is_empty())
+            || self.0.issuer.is_some()
+        {
+            Ok(self.0)
+        } else {
+            Err("Failed to build request! Account name or issuer required!".into())
+        }
+    }
+}
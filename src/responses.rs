@@ -1,16 +1,46 @@
 use crate::mytoken::{Capability, MyTokenType, Restriction, Rotation};
-use crate::{Response, Token};
+use crate::{AccountConfig, Response, Token};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::Display;
 use url::Url;
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-#[serde(rename_all = "lowercase")]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
 pub enum Status {
+    ///The request succeeded.
     SUCCESS,
+    ///The request failed, the response holds the error.
     FAILURE,
+    ///The request started a flow that must be continued, e.g. the device flow.
+    ACCEPTED,
+    ///The looked up flow state does not exist (yet).
+    NOTFOUND,
+    ///The looked up flow state was found, but the flow has already been finished.
+    FOUNDBUTDONE,
+    ///Status not known to this crate, e.g. introduced by a newer agent.
+    UNKNOWN(String),
+}
+
+impl From<String> for Status {
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "success" => Self::SUCCESS,
+            "failure" => Self::FAILURE,
+            "accepted" => Self::ACCEPTED,
+            "NotFound" => Self::NOTFOUND,
+            "FoundButDone" => Self::FOUNDBUTDONE,
+            _ => Self::UNKNOWN(status),
+        }
+    }
+}
+
+impl From<Status> for String {
+    fn from(status: Status) -> Self {
+        status.to_string()
+    }
 }
 
 impl Display for Status {
@@ -18,10 +48,19 @@ impl Display for Status {
         match self {
             Self::SUCCESS => write!(f, "success"),
             Self::FAILURE => write!(f, "failure"),
+            Self::ACCEPTED => write!(f, "accepted"),
+            Self::NOTFOUND => write!(f, "NotFound"),
+            Self::FOUNDBUTDONE => write!(f, "FoundButDone"),
+            Self::UNKNOWN(status) => write!(f, "{}", status),
         }
     }
 }
 
+/// Untyped response returned for the [`crate::requests::GenericRequest`].
+impl Response for serde_json::Value {
+    const ACCEPTS_ACCEPTED: bool = true;
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct OIDCAgentResponse {
     status: Status,
@@ -33,7 +72,72 @@ impl OIDCAgentResponse {
     }
 }
 
+/// Response of the requests that only report whether the operation succeeded.
 #[derive(Serialize, Deserialize, Debug)]
+pub struct BasicResponse {
+    info: Option<String>,
+}
+
+impl BasicResponse {
+    pub fn info(&self) -> Option<&String> {
+        self.info.as_ref()
+    }
+}
+
+impl Response for BasicResponse {}
+
+/// Response of the [`crate::requests::CheckRequest`]. Older agents answer the request without
+/// any details, so every field is optional.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StatusResponse {
+    version: Option<String>,
+    locked: Option<bool>,
+    loaded_accounts: Option<usize>,
+}
+
+impl StatusResponse {
+    pub fn version(&self) -> Option<&String> {
+        self.version.as_ref()
+    }
+    pub fn locked(&self) -> Option<bool> {
+        self.locked
+    }
+    pub fn loaded_accounts(&self) -> Option<usize> {
+        self.loaded_accounts
+    }
+    /// Checks whether the reported agent version is at least `major.minor.patch`. A pre-release,
+    /// e.g. `5.2.0-rc.1`, precedes its release, the build metadata is ignored. Returns `false` if
+    /// the agent did not report its version or the version cannot be parsed.
+    pub fn version_at_least(&self, major: u64, minor: u64, patch: u64) -> bool {
+        let Some(version) = &self.version else {
+            return false;
+        };
+        let version = version.trim_start_matches('v');
+        let version = version
+            .split_once('+')
+            .map_or(version, |(version, _)| version);
+        let (core, pre_release) = match version.split_once('-') {
+            Some((_, "")) => return false,
+            Some((core, pre_release)) => (core, Some(pre_release)),
+            None => (version, None),
+        };
+        let parsed: Option<Vec<u64>> = core.split('.').map(|part| part.parse().ok()).collect();
+        let current = match parsed.as_deref() {
+            Some([ma, mi, pa]) => (*ma, *mi, *pa),
+            Some([ma, mi]) => (*ma, *mi, 0),
+            Some([ma]) => (*ma, 0, 0),
+            _ => return false,
+        };
+        match pre_release {
+            Some(_) => current > (major, minor, patch),
+            None => current >= (major, minor, patch),
+        }
+    }
+}
+
+impl Response for StatusResponse {}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AccessTokenResponse {
     access_token: Token,
     issuer: Url,
@@ -43,6 +147,13 @@ pub struct AccessTokenResponse {
 }
 
 impl AccessTokenResponse {
+    pub(crate) fn new(access_token: Token, issuer: Url, expires_at: DateTime<Utc>) -> Self {
+        Self {
+            access_token,
+            issuer,
+            expires_at,
+        }
+    }
     pub fn access_token(&self) -> &Token {
         &self.access_token
     }
@@ -56,6 +167,23 @@ impl AccessTokenResponse {
 
 impl Response for AccessTokenResponse {}
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IdTokenResponse {
+    id_token: Token,
+    issuer: Url,
+}
+
+impl IdTokenResponse {
+    pub fn id_token(&self) -> &Token {
+        &self.id_token
+    }
+    pub fn issuer(&self) -> &Url {
+        &self.issuer
+    }
+}
+
+impl Response for IdTokenResponse {}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MyTokenResponse {
     mytoken: Token,
@@ -123,3 +251,329 @@ impl AccountsResponse {
 }
 
 impl Response for AccountsResponse {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccountState {
+    loaded: bool,
+    has_pubclient: bool,
+}
+
+impl AccountState {
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+    pub fn has_public_client(&self) -> bool {
+        self.has_pubclient
+    }
+}
+
+/// Flattened view of a single entry from the [`AccountInfoResponse`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountInfo {
+    shortname: String,
+    issuer: Url,
+    state: AccountState,
+}
+
+impl AccountInfo {
+    pub fn shortname(&self) -> &str {
+        &self.shortname
+    }
+    pub fn issuer(&self) -> &Url {
+        &self.issuer
+    }
+    pub fn is_loaded(&self) -> bool {
+        self.state.is_loaded()
+    }
+    pub fn has_public_client(&self) -> bool {
+        self.state.has_public_client()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AccountInfoResponse {
+    info: BTreeMap<Url, BTreeMap<String, AccountState>>,
+}
+
+impl AccountInfoResponse {
+    /// Returns the raw account information grouped by the issuer url and the account shortname.
+    pub fn info(&self) -> &BTreeMap<Url, BTreeMap<String, AccountState>> {
+        &self.info
+    }
+    /// Returns every configured account, loaded or not, sorted by the issuer and the shortname.
+    pub fn accounts(&self) -> Vec<AccountInfo> {
+        self.info
+            .iter()
+            .flat_map(|(issuer, accounts)| {
+                accounts.iter().map(|(shortname, state)| AccountInfo {
+                    shortname: shortname.clone(),
+                    issuer: issuer.clone(),
+                    state: state.clone(),
+                })
+            })
+            .collect()
+    }
+    /// Returns the accounts configured for the given `issuer`.
+    pub fn accounts_for_issuer(&self, issuer: &Url) -> Vec<AccountInfo> {
+        self.accounts()
+            .into_iter()
+            .filter(|a| a.issuer() == issuer)
+            .collect()
+    }
+}
+
+impl Response for AccountInfoResponse {}
+
+/// Device authorization returned by the issuer when the device flow is started.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeviceAuthorization {
+    device_code: Token,
+    user_code: String,
+    verification_uri: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verification_uri_complete: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_in: Option<u64>, //Always seconds
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u64>, //Always seconds
+}
+
+impl DeviceAuthorization {
+    pub fn device_code(&self) -> &Token {
+        &self.device_code
+    }
+    pub fn user_code(&self) -> &str {
+        &self.user_code
+    }
+    pub fn verification_uri(&self) -> &str {
+        &self.verification_uri
+    }
+    pub fn verification_uri_complete(&self) -> Option<&String> {
+        self.verification_uri_complete.as_ref()
+    }
+    pub fn expires_in(&self) -> Option<u64> {
+        self.expires_in
+    }
+    pub fn interval(&self) -> Option<u64> {
+        self.interval
+    }
+}
+
+/// Response of the account generation requests. Depending on the `status` it either holds the
+/// generated account `config` or the data needed to continue the flow.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GenResponse {
+    status: Status,
+    config: Option<AccountConfig>,
+    oidc_device: Option<DeviceAuthorization>,
+    uri: Option<Url>,
+    state: Option<String>,
+}
+
+impl GenResponse {
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+    pub fn config(&self) -> Option<&AccountConfig> {
+        self.config.as_ref()
+    }
+    pub fn device(&self) -> Option<&DeviceAuthorization> {
+        self.oidc_device.as_ref()
+    }
+    pub fn uri(&self) -> Option<&Url> {
+        self.uri.as_ref()
+    }
+    pub fn state(&self) -> Option<&String> {
+        self.state.as_ref()
+    }
+}
+
+impl Response for GenResponse {
+    const ACCEPTS_ACCEPTED: bool = true;
+}
+
+/// Client registered at the issuer by the agent.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegisteredClient {
+    client_id: String,
+    client_secret: Option<Token>,
+    registration_access_token: Option<Token>,
+    registration_client_uri: Option<Url>,
+    redirect_uris: Option<Vec<String>>,
+    scope: Option<String>,
+}
+
+impl RegisteredClient {
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+    pub fn client_secret(&self) -> Option<&Token> {
+        self.client_secret.as_ref()
+    }
+    pub fn registration_access_token(&self) -> Option<&Token> {
+        self.registration_access_token.as_ref()
+    }
+    pub fn registration_client_uri(&self) -> Option<&Url> {
+        self.registration_client_uri.as_ref()
+    }
+    pub fn redirect_uris(&self) -> Option<&Vec<String>> {
+        self.redirect_uris.as_ref()
+    }
+    pub fn scope(&self) -> Option<&String> {
+        self.scope.as_ref()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RegisterResponse {
+    client: RegisteredClient,
+}
+
+impl RegisterResponse {
+    pub fn client(&self) -> &RegisteredClient {
+        &self.client
+    }
+}
+
+impl Response for RegisterResponse {}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ScopesResponse {
+    info: String,
+}
+
+impl ScopesResponse {
+    /// Returns the raw, space separated list of supported scopes.
+    pub fn info(&self) -> &str {
+        &self.info
+    }
+    /// Returns the set of scopes supported by the issuer.
+    pub fn scopes(&self) -> HashSet<String> {
+        self.info.split_whitespace().map(String::from).collect()
+    }
+}
+
+impl Response for ScopesResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(version: Option<&str>) -> StatusResponse {
+        StatusResponse {
+            version: version.map(String::from),
+            locked: None,
+            loaded_accounts: None,
+        }
+    }
+
+    #[test]
+    fn parses_status() {
+        let response: StatusResponse = serde_json::from_str(
+            r#"{"status": "success", "version": "5.2.3", "locked": false, "loaded_accounts": 2}"#,
+        )
+        .unwrap();
+        assert_eq!(response.version().unwrap(), "5.2.3");
+        assert_eq!(response.locked(), Some(false));
+        assert_eq!(response.loaded_accounts(), Some(2));
+        assert!(response.version_at_least(5, 2, 0));
+
+        let response: StatusResponse =
+            serde_json::from_str(r#"{"status": "success", "info": "Agent running"}"#).unwrap();
+        assert_eq!(response.version(), None);
+        assert_eq!(response.locked(), None);
+        assert_eq!(response.loaded_accounts(), None);
+        assert!(!response.version_at_least(0, 0, 0));
+    }
+
+    #[test]
+    fn compares_versions() {
+        let cases = [
+            ("5.2.3", (5, 2, 3), true),
+            ("5.2.3", (5, 2, 4), false),
+            ("5.2.3", (5, 1, 9), true),
+            ("5.2.3", (4, 9, 9), true),
+            ("5.10.0", (5, 9, 0), true),
+            ("v5.2.3", (5, 2, 3), true),
+            ("5.2", (5, 2, 0), true),
+            ("5.2", (5, 2, 1), false),
+            ("6", (5, 9, 9), true),
+            ("5.2.3+debian1", (5, 2, 3), true),
+            ("5.3.0-rc.1", (5, 3, 0), false),
+            ("5.3.0-rc.1", (5, 2, 9), true),
+            ("5.3.0-rc.1+build.7", (5, 3, 0), false),
+            ("", (0, 0, 0), false),
+            ("unknown", (0, 0, 0), false),
+            ("5.x.0", (5, 0, 0), false),
+            ("5..0", (5, 0, 0), false),
+            ("5.2.3.4", (5, 2, 3), false),
+            ("5.2.3-", (5, 2, 3), false),
+            ("-5.2.3", (0, 0, 0), false),
+        ];
+        for (version, (major, minor, patch), expected) in cases {
+            assert_eq!(
+                status(Some(version)).version_at_least(major, minor, patch),
+                expected,
+                "{version} >= {major}.{minor}.{patch}"
+            );
+        }
+        assert!(!status(None).version_at_least(0, 0, 0));
+    }
+
+    #[test]
+    fn parses_account_info() {
+        let response: AccountInfoResponse = serde_json::from_str(
+            r#"{
+                "status": "success",
+                "info": {
+                    "https://login.helmholtz.de/oauth2/": {
+                        "helmholtz": {"loaded": false, "has_pubclient": true}
+                    },
+                    "https://aai.egi.eu/auth/realms/egi/": {
+                        "egi": {"loaded": true, "has_pubclient": false},
+                        "egi-dev": {"loaded": false, "has_pubclient": false}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let accounts: Vec<_> = response
+            .accounts()
+            .iter()
+            .map(|a| {
+                (
+                    a.issuer().to_string(),
+                    a.shortname().to_string(),
+                    a.is_loaded(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            accounts,
+            vec![
+                (
+                    "https://aai.egi.eu/auth/realms/egi/".to_string(),
+                    "egi".to_string(),
+                    true
+                ),
+                (
+                    "https://aai.egi.eu/auth/realms/egi/".to_string(),
+                    "egi-dev".to_string(),
+                    false
+                ),
+                (
+                    "https://login.helmholtz.de/oauth2/".to_string(),
+                    "helmholtz".to_string(),
+                    false
+                ),
+            ]
+        );
+        let helmholtz = Url::parse("https://login.helmholtz.de/oauth2/").unwrap();
+        let accounts = response.accounts_for_issuer(&helmholtz);
+        assert_eq!(accounts.len(), 1);
+        assert!(accounts[0].has_public_client());
+    }
+}
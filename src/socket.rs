@@ -0,0 +1,208 @@
+use socket2::{Domain, SockAddr, Socket, Type};
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::io;
+use std::os::fd::OwnedFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Address of the agent socket.
+///
+/// Values starting with `@`, e.g. `@oidc-agent`, denote sockets in the Linux abstract namespace.
+/// On other systems they are treated as regular paths.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SocketAddress {
+    /// Socket bound to a file system path.
+    Path(PathBuf),
+    /// Socket bound to a name in the Linux abstract namespace, stored without the leading `@`.
+    Abstract(Vec<u8>),
+}
+
+impl SocketAddress {
+    /// Returns the file system path of the socket, `None` for abstract sockets.
+    pub fn as_path(&self) -> Option<&Path> {
+        match self {
+            Self::Path(path) => Some(path),
+            Self::Abstract(_) => None,
+        }
+    }
+
+    /// Checks whether the socket is in the Linux abstract namespace.
+    pub fn is_abstract(&self) -> bool {
+        matches!(self, Self::Abstract(_))
+    }
+
+    /// Connects to the socket, giving up after the `timeout` if one is set.
+    pub(crate) fn connect(&self, timeout: Option<Duration>) -> io::Result<UnixStream> {
+        match (self, timeout) {
+            (Self::Path(path), None) => UnixStream::connect(path),
+            (Self::Abstract(name), None) => connect_abstract(name),
+            (_, Some(timeout)) => {
+                let socket = Socket::new(Domain::UNIX, Type::STREAM, None)?;
+                socket.connect_timeout(&self.to_sock_addr()?, timeout)?;
+                Ok(UnixStream::from(OwnedFd::from(socket)))
+            }
+        }
+    }
+
+    /// Connects to the socket asynchronously.
+    #[cfg(feature = "async")]
+    pub(crate) async fn connect_async(&self) -> io::Result<tokio::net::UnixStream> {
+        match self {
+            Self::Path(path) => tokio::net::UnixStream::connect(path).await,
+            Self::Abstract(name) => {
+                let mut path = vec![0];
+                path.extend_from_slice(name);
+                tokio::net::UnixStream::connect(OsStr::from_bytes(&path)).await
+            }
+        }
+    }
+
+    fn to_sock_addr(&self) -> io::Result<SockAddr> {
+        match self {
+            Self::Path(path) => SockAddr::unix(path),
+            Self::Abstract(name) => {
+                let mut path = vec![0];
+                path.extend_from_slice(name);
+                SockAddr::unix(OsStr::from_bytes(&path))
+            }
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn connect_abstract(name: &[u8]) -> io::Result<UnixStream> {
+    #[cfg(target_os = "android")]
+    use std::os::android::net::SocketAddrExt;
+    #[cfg(target_os = "linux")]
+    use std::os::linux::net::SocketAddrExt;
+
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+    UnixStream::connect_addr(&addr)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn connect_abstract(_name: &[u8]) -> io::Result<UnixStream> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "abstract sockets are supported only on Linux",
+    ))
+}
+
+impl From<&str> for SocketAddress {
+    fn from(address: &str) -> Self {
+        match address.strip_prefix('@') {
+            Some(name) if cfg!(any(target_os = "linux", target_os = "android")) => {
+                Self::Abstract(name.as_bytes().to_vec())
+            }
+            _ => Self::Path(PathBuf::from(address)),
+        }
+    }
+}
+
+impl From<&Path> for SocketAddress {
+    fn from(path: &Path) -> Self {
+        match path.to_str() {
+            Some(address) => Self::from(address),
+            None => Self::Path(path.to_path_buf()),
+        }
+    }
+}
+
+impl From<PathBuf> for SocketAddress {
+    fn from(path: PathBuf) -> Self {
+        Self::from(path.as_path())
+    }
+}
+
+impl Display for SocketAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Abstract(name) => write!(f, "@{}", String::from_utf8_lossy(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn parses_abstract_address() {
+        let address = SocketAddress::from("@oidc-agent");
+        assert_eq!(address, SocketAddress::Abstract(b"oidc-agent".to_vec()));
+        assert!(address.is_abstract());
+        assert_eq!(address.as_path(), None);
+        assert_eq!(address.to_string(), "@oidc-agent");
+        assert_eq!(SocketAddress::from(Path::new("@oidc-agent")), address);
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn parses_abstract_address_as_path() {
+        let address = SocketAddress::from("@oidc-agent");
+        assert_eq!(address, SocketAddress::Path(PathBuf::from("@oidc-agent")));
+        assert!(!address.is_abstract());
+    }
+
+    #[test]
+    fn parses_path_address() {
+        let path = "/tmp/oidc-agent-service-1000/oidc-agent.sock";
+        let address = SocketAddress::from(path);
+        assert_eq!(address, SocketAddress::Path(PathBuf::from(path)));
+        assert_eq!(address.as_path(), Some(Path::new(path)));
+        assert!(!address.is_abstract());
+        assert_eq!(address.to_string(), path);
+        assert_eq!(SocketAddress::from(PathBuf::from(path)), address);
+        assert_eq!(
+            SocketAddress::from("relative/@agent.sock"),
+            SocketAddress::Path(PathBuf::from("relative/@agent.sock"))
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn abstract_listener(name: &str) -> (SocketAddress, std::os::unix::net::UnixListener) {
+        #[cfg(target_os = "android")]
+        use std::os::android::net::SocketAddrExt;
+        #[cfg(target_os = "linux")]
+        use std::os::linux::net::SocketAddrExt;
+
+        let name = format!("oidc-agent-rs-test-{}-{}", std::process::id(), name);
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(&name).unwrap();
+        let listener = std::os::unix::net::UnixListener::bind_addr(&addr).unwrap();
+        (SocketAddress::from(format!("@{}", name).as_str()), listener)
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn connects_to_abstract_socket() {
+        #[cfg(target_os = "android")]
+        use std::os::android::net::SocketAddrExt;
+        #[cfg(target_os = "linux")]
+        use std::os::linux::net::SocketAddrExt;
+
+        let (address, listener) = abstract_listener("sync");
+        let SocketAddress::Abstract(name) = &address else {
+            panic!("not an abstract address: {address}");
+        };
+        for timeout in [None, Some(Duration::from_secs(5))] {
+            let stream = address.connect(timeout).unwrap();
+            listener.accept().unwrap();
+            let peer = stream.peer_addr().unwrap();
+            assert_eq!(peer.as_abstract_name(), Some(name.as_slice()));
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "async", any(target_os = "linux", target_os = "android")))]
+    async fn connects_to_abstract_socket_async() {
+        let (address, listener) = abstract_listener("tokio");
+        let stream = address.connect_async().await.unwrap();
+        listener.accept().unwrap();
+        assert!(stream.peer_addr().is_ok());
+    }
+}
@@ -0,0 +1,446 @@
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use crate::builder::AgentOptions;
+use crate::codec::{MessageDecoder, READ_CHUNK};
+use crate::process::private_socket_dir;
+use crate::socket::SocketAddress;
+use crate::{Agent, AgentBuilder, AgentResult};
+
+type Handler = Arc<dyn Fn(&Value) -> Value + Send + Sync>;
+
+#[derive(Default)]
+struct Script {
+    once: HashMap<String, VecDeque<Value>>,
+    handlers: HashMap<String, Handler>,
+    received: Vec<Value>,
+}
+
+/// Scripted answer to a single request.
+enum Answer {
+    Response(Value),
+    Handler(Handler),
+}
+
+impl Script {
+    /// Records the `request` and looks up its answer. The handlers are called after the script is
+    /// unlocked, so the requests of concurrent connections are answered concurrently.
+    fn answer(&mut self, request: &Value) -> Answer {
+        let request_type = request
+            .get("request")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        self.received.push(request.clone());
+        if let Some(response) = self
+            .once
+            .get_mut(&request_type)
+            .and_then(VecDeque::pop_front)
+        {
+            return Answer::Response(response);
+        }
+        match self.handlers.get(&request_type) {
+            Some(handler) => Answer::Handler(handler.clone()),
+            None => Answer::Response(failure(format!(
+                "No response scripted for the request '{}'",
+                request_type
+            ))),
+        }
+    }
+}
+
+/// Fake oidc-agent answering the requests from a script, for testing code built on this crate
+/// without a running agent.
+///
+/// The mock listens on a socket in a new private temporary directory. Requests without a scripted
+/// response are answered with the `failure` status. Every received request is recorded. The
+/// socket is removed when the `MockAgent` is dropped. A message that is not valid JSON is answered
+/// with the `failure` status and the connection is closed.
+///
+/// The mock implements the [`crate::TokenProvider`] and, with the `async` feature, the
+/// [`crate::async_impl::AsyncTokenProvider`], so it can be passed to the code taking a provider.
+///
+/// # Examples
+/// ```ignore
+/// let mock = MockAgent::start()?;
+/// mock.access_token("token", "https://issuer.url", 1_700_000_000);
+///
+/// let agent = mock.agent()?;
+/// let request = AccessTokenRequest::builder()
+///     .account("profile_shortname")
+///     .min_valid_period(60)
+///     .build()?;
+/// assert_eq!(agent.send_request(request)?.access_token().secret(), "token");
+/// assert_eq!(mock.last_request().unwrap()["min_valid_period"], 60);
+/// ```
+pub struct MockAgent {
+    socket_dir: PathBuf,
+    socket_path: PathBuf,
+    script: Arc<Mutex<Script>>,
+    shutdown: Arc<AtomicBool>,
+    server: Option<JoinHandle<()>>,
+    agent: Agent,
+    #[cfg(feature = "async")]
+    async_agent: crate::async_impl::Agent,
+}
+
+impl MockAgent {
+    /// Attempts to start a new `MockAgent` with an empty script.
+    /// # Errors
+    /// The method returns an coresponding [`crate::Error`] if:
+    /// - creating the socket directory or binding the socket fails.
+    pub fn start() -> AgentResult<Self> {
+        let socket_dir = private_socket_dir()?;
+        let socket_path = socket_dir.join("oidc-agent.sock");
+        let listener = match UnixListener::bind(&socket_path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&socket_dir);
+                return Err(e.into());
+            }
+        };
+        let script = Arc::new(Mutex::new(Script::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let server = {
+            let script = script.clone();
+            let shutdown = shutdown.clone();
+            thread::spawn(move || serve(listener, script, shutdown))
+        };
+        let socket = SocketAddress::Path(socket_path.clone());
+        Ok(Self {
+            socket_dir,
+            socket_path,
+            script,
+            shutdown,
+            server: Some(server),
+            agent: Agent::from_parts(socket.clone(), AgentOptions::default()),
+            #[cfg(feature = "async")]
+            async_agent: crate::async_impl::Agent::from_parts(socket, AgentOptions::default()),
+        })
+    }
+
+    /// Returns the path of the mock socket.
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Creates a new [`AgentBuilder`] with the mock socket set. Use it to build the
+    /// [`crate::async_impl::Agent`] or to change the agent settings.
+    pub fn builder(&self) -> AgentBuilder {
+        AgentBuilder::new().socket_path(&self.socket_path)
+    }
+
+    /// Attempts to build the [`Agent`] connected to the mock.
+    /// # Errors
+    /// The same as [`AgentBuilder::build`].
+    pub fn agent(&self) -> AgentResult<Agent> {
+        self.builder().build()
+    }
+
+    /// Answers every request of the `request_type`, e.g. `access_token`, with the `response`.
+    pub fn respond(&self, request_type: &str, response: Value) -> &Self {
+        self.respond_with(request_type, move |_| response.clone())
+    }
+
+    /// Answers the next request of the `request_type` with the `response`. Queued responses are
+    /// used in order before the ones set by the [`MockAgent::respond`] or the
+    /// [`MockAgent::respond_with`].
+    pub fn respond_once(&self, request_type: &str, response: Value) -> &Self {
+        self.script()
+            .once
+            .entry(request_type.to_string())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// Answers every request of the `request_type` with the result of the `handler` called with
+    /// the received request. Every connection is served by its own thread, so the `handler` may be
+    /// called concurrently.
+    pub fn respond_with<F>(&self, request_type: &str, handler: F) -> &Self
+    where
+        F: Fn(&Value) -> Value + Send + Sync + 'static,
+    {
+        self.script()
+            .handlers
+            .insert(request_type.to_string(), Arc::new(handler));
+        self
+    }
+
+    /// Answers every `access_token` request with the `access_token`.
+    pub fn access_token(&self, access_token: &str, issuer: &str, expires_at: i64) -> &Self {
+        self.respond(
+            "access_token",
+            json!({
+                "status": "success",
+                "access_token": access_token,
+                "issuer": issuer,
+                "expires_at": expires_at,
+            }),
+        )
+    }
+
+    /// Answers every `mytoken` request with the `mytoken`.
+    pub fn mytoken(&self, mytoken: &str, mytoken_issuer: &str, oidc_issuer: &str) -> &Self {
+        self.respond(
+            "mytoken",
+            json!({
+                "status": "success",
+                "mytoken": mytoken,
+                "mytoken_issuer": mytoken_issuer,
+                "oidc_issuer": oidc_issuer,
+            }),
+        )
+    }
+
+    /// Answers every `loaded_accounts` request with the `accounts`.
+    pub fn loaded_accounts(&self, accounts: &[&str]) -> &Self {
+        self.respond(
+            "loaded_accounts",
+            json!({
+                "status": "success",
+                "info": accounts,
+            }),
+        )
+    }
+
+    /// Returns every request received so far, in order.
+    pub fn received(&self) -> Vec<Value> {
+        self.script().received.clone()
+    }
+
+    /// Returns the last received request.
+    pub fn last_request(&self) -> Option<Value> {
+        self.script().received.last().cloned()
+    }
+
+    /// Forgets the received requests.
+    pub fn clear_received(&self) {
+        self.script().received.clear();
+    }
+
+    /// Returns the agent connected to the mock used by the token provider implementations.
+    pub(crate) fn provider_agent(&self) -> &Agent {
+        &self.agent
+    }
+
+    /// Asynchronous version of the [`MockAgent::provider_agent`].
+    #[cfg(feature = "async")]
+    pub(crate) fn async_provider_agent(&self) -> &crate::async_impl::Agent {
+        &self.async_agent
+    }
+
+    fn script(&self) -> MutexGuard<'_, Script> {
+        lock(&self.script)
+    }
+}
+
+impl Drop for MockAgent {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wakes up the server blocked in accept.
+        let _ = UnixStream::connect(&self.socket_path);
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+        let _ = fs::remove_dir_all(&self.socket_dir);
+    }
+}
+
+impl std::fmt::Debug for MockAgent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockAgent")
+            .field("socket_path", &self.socket_path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Locks the script, ignoring the poisoning by a panicking handler.
+fn lock(script: &Mutex<Script>) -> MutexGuard<'_, Script> {
+    script.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn serve(listener: UnixListener, script: Arc<Mutex<Script>>, shutdown: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        if let Ok(stream) = stream {
+            let script = script.clone();
+            thread::spawn(move || handle(stream, &script));
+        }
+    }
+}
+
+fn failure(error: String) -> Value {
+    json!({"status": "failure", "error": error})
+}
+
+/// Answers the requests sent over one connection until the client closes it or sends invalid
+/// JSON.
+fn handle(mut stream: UnixStream, script: &Mutex<Script>) {
+    let mut decoder = MessageDecoder::default();
+    let mut chunk = [0; READ_CHUNK];
+    loop {
+        loop {
+            let message = match decoder.next_message() {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = stream.write_all(failure(e.to_string()).to_string().as_bytes());
+                    return;
+                }
+            };
+            let response = match serde_json::from_slice(&message) {
+                Ok(request) => {
+                    let answer = lock(script).answer(&request);
+                    match answer {
+                        Answer::Response(response) => response,
+                        Answer::Handler(handler) => handler(&request),
+                    }
+                }
+                Err(e) => failure(e.to_string()),
+            };
+            if stream.write_all(response.to_string().as_bytes()).is_err() {
+                return;
+            }
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => return,
+            Ok(read) => decoder.feed(&chunk[..read]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mytoken::{Capability, Profile, Restriction};
+    use crate::requests::{AccessTokenRequest, MyTokenRequest};
+    use crate::AccountConfig;
+    use std::sync::Barrier;
+    use std::time::Duration;
+
+    #[test]
+    fn records_access_token_request() {
+        let mock = MockAgent::start().unwrap();
+        mock.access_token("token", "https://issuer.example/", 1_700_000_000);
+        let agent = mock.builder().application_hint("tests").build().unwrap();
+        let request = AccessTokenRequest::builder()
+            .account("profile")
+            .min_valid_period(60)
+            .add_scope("openid")
+            .add_scope("profile")
+            .audience("https://api.example")
+            .build()
+            .unwrap();
+        let response = agent.send_request(request).unwrap();
+        assert_eq!(response.access_token().secret(), "token");
+        assert_eq!(
+            mock.last_request().unwrap(),
+            json!({
+                "request": "access_token",
+                "account": "profile",
+                "min_valid_period": 60,
+                "application_hint": "tests",
+                "scope": "openid profile",
+                "audience": "https://api.example",
+            })
+        );
+    }
+
+    #[test]
+    fn records_mytoken_request() {
+        let mock = MockAgent::start().unwrap();
+        mock.mytoken(
+            "mytoken",
+            "https://mytoken.example/",
+            "https://issuer.example/",
+        );
+        let agent = mock.agent().unwrap();
+        let profile = Profile::builder()
+            .add_capabilities(&[Capability::AT])
+            .add_restrictions(&[Restriction::builder().usages_AT(5).build()])
+            .build();
+        let request = MyTokenRequest::builder("profile")
+            .mytoken_profile(&profile)
+            .application_hint("tests")
+            .build()
+            .unwrap();
+        let response = agent.send_request(request).unwrap();
+        assert_eq!(response.mytoken().secret(), "mytoken");
+        assert_eq!(
+            mock.last_request().unwrap(),
+            json!({
+                "request": "mytoken",
+                "account": "profile",
+                "mytoken_profile": {
+                    "capabilities": ["AT"],
+                    "restrictions": [{"usages_AT": 5}],
+                },
+                "application_hint": "tests",
+            })
+        );
+    }
+
+    #[test]
+    fn answers_invalid_json_and_closes() {
+        let mock = MockAgent::start().unwrap();
+        let mut stream = UnixStream::connect(mock.socket_path()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream.write_all(b"{bad json}").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["status"], "failure");
+
+        mock.loaded_accounts(&["profile"]);
+        let accounts = mock.agent().unwrap().get_loaded_accounts().unwrap();
+        assert_eq!(accounts, vec!["profile"]);
+    }
+
+    #[test]
+    fn add_account_requires_shortname() {
+        let mock = MockAgent::start().unwrap();
+        mock.respond("add", json!({"status": "success"}));
+        let agent = mock.agent().unwrap();
+        let config = AccountConfig::from_json(r#"{"issuer_url": "https://issuer.example/"}"#);
+        assert!(agent.add_account(&config.unwrap()).is_err());
+        assert!(mock.received().is_empty());
+
+        let config = AccountConfig::from_json(r#"{"name": "profile"}"#).unwrap();
+        agent.add_account(&config).unwrap();
+        assert_eq!(mock.last_request().unwrap()["config"]["name"], "profile");
+    }
+
+    #[test]
+    fn answers_connections_concurrently() {
+        let mock = MockAgent::start().unwrap();
+        // Each handler waits for the other one, so both requests must be handled at once.
+        let barrier = Arc::new(Barrier::new(2));
+        mock.respond_with("check", move |_| {
+            barrier.wait();
+            json!({"status": "success"})
+        });
+        let agent = mock
+            .builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        thread::scope(|scope| {
+            let checks: Vec<_> = (0..2).map(|_| scope.spawn(|| agent.status())).collect();
+            for check in checks {
+                check.join().unwrap().unwrap();
+            }
+        });
+    }
+}
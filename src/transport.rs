@@ -0,0 +1,83 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+
+use crate::builder::AgentOptions;
+use crate::peer;
+use crate::socket::SocketAddress;
+use crate::AgentResult;
+
+/// Channel carrying the messages between the [`crate::Agent`] and the oidc-agent.
+///
+/// The agent opens a new stream for every request, or once per [`crate::Connection`]. The stream
+/// must pass the messages unchanged, e.g. a Unix socket, an SSH channel forwarded to the agent
+/// socket or an in-memory pipe. Use the [`crate::Agent::with_transport`] to build an agent on a
+/// custom transport, or the [`crate::codec`] directly if the IO does not fit the blocking
+/// [`Read`] and [`Write`].
+///
+/// # Examples
+/// ```ignore
+/// struct SshTransport(ssh2::Session);
+///
+/// impl Transport for SshTransport {
+///     type Stream = ssh2::Stream;
+///     fn connect(&self) -> AgentResult<Self::Stream> {
+///         let channel = self.0.channel_direct_streamlocal("/run/oidc-agent.sock", None)?;
+///         Ok(channel.stream(0))
+///     }
+/// }
+///
+/// let agent = Agent::with_transport(SshTransport(session));
+/// ```
+pub trait Transport {
+    type Stream: Read + Write;
+
+    /// Attempts to open a new stream to the agent.
+    fn connect(&self) -> AgentResult<Self::Stream>;
+}
+
+/// Default transport connecting to the agent Unix socket.
+#[derive(Debug, Clone)]
+pub struct UnixTransport {
+    socket: SocketAddress,
+    pub(crate) options: AgentOptions,
+}
+
+impl UnixTransport {
+    /// Creates a new transport connecting to the `socket` with the default options, i.e. no
+    /// timeouts and the peer verification enabled. Use the [`crate::AgentBuilder`] to change them.
+    pub fn new(socket: SocketAddress) -> Self {
+        Self::with_options(socket, AgentOptions::default())
+    }
+
+    pub(crate) fn with_options(socket: SocketAddress, options: AgentOptions) -> Self {
+        Self { socket, options }
+    }
+
+    /// Returns the agent socket address.
+    pub fn socket(&self) -> &SocketAddress {
+        &self.socket
+    }
+
+    /// Verifies the connected `stream` if the peer verification is enabled.
+    pub(crate) fn verify<S: std::os::fd::AsRawFd>(&self, stream: &S) -> AgentResult<()> {
+        if self.options.verify_peer {
+            if let Some(path) = self.socket.as_path() {
+                peer::verify_socket_file(path)?;
+            }
+            peer::verify_peer(stream)?;
+        }
+        Ok(())
+    }
+}
+
+impl Transport for UnixTransport {
+    type Stream = UnixStream;
+
+    fn connect(&self) -> AgentResult<UnixStream> {
+        let stream = self.socket.connect(self.options.connect_timeout)?;
+        self.verify(&stream)?;
+        stream.set_read_timeout(self.options.read_timeout)?;
+        stream.set_write_timeout(self.options.write_timeout)?;
+        Ok(stream)
+    }
+}